@@ -2399,13 +2399,14 @@ pub const DEFAULT_IMAGES: &[&str] = &[
 mod tests {
     use super::{DEFAULT_IMAGES, IMAGE_KNOWN_SIGNATURES};
     use crate::image::Image;
+    use rand::thread_rng;
 
     #[test]
     fn test_parsing() {
         assert_eq!(
             DEFAULT_IMAGES
                 .iter()
-                .map(|i| { (i, Image::from_yaml(i)) })
+                .map(|i| { (i, Image::from_yaml(i, &mut thread_rng())) })
                 .inspect(|(i, e)| if e.is_err() {
                     eprintln!("Error:{:?}\n\nImage:\n{}", e, i)
                 })
@@ -2426,8 +2427,7 @@ mod tests {
                     // Skip first char in line.
                     let _ = it.next();
                     // We want the second char.
-                    let second_char = it.next().unwrap_or(' ');
-                    second_char
+                    it.next().unwrap_or(' ')
                 })
                 .any(|second_char| !second_char.is_whitespace())
             {
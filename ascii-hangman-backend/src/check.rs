@@ -0,0 +1,105 @@
+//! Finds problems in configuration files before they are used in class.
+
+use crate::dictionary::ConfigParseError;
+use crate::dictionary::Dict;
+use crate::dictionary::CONF_LINE_SECRET_MODIFIER_VISIBLE;
+use crate::image::Image;
+use crate::secret::{Secret, SecretConf, LINE_WIDTH};
+use std::collections::HashSet;
+
+/// Checks the configuration `config` and every secret in it. Returns one
+/// message per problem found, none when everything is fine.
+pub fn check(config: &str) -> Vec<String> {
+    let dict = match Dict::from(config) {
+        Ok(dict) => dict,
+        Err(e) => return vec![e.locate(config).to_string()],
+    };
+    let conf = match SecretConf::from_yaml(config) {
+        Ok(conf) => conf,
+        Err(e) => return vec![e.locate(config).to_string()],
+    };
+
+    let mut problems = Vec::new();
+    match Image::from_yaml(config, &mut rand::thread_rng()) {
+        Ok(_) | Err(ConfigParseError::NoImageData) => {}
+        Err(e) => problems.push(e.locate(config).to_string()),
+    }
+
+    for (lesson, secrets) in dict.secret_lists() {
+        let mut seen = HashSet::new();
+        for entry in secrets {
+            let secret = Secret::new(&entry.text, &conf);
+            let mut problem = |message: String| {
+                problems.push(match lesson {
+                    Some(lesson) => format!("Lesson `{}`: `{}` {}", lesson, entry.text, message),
+                    None => format!("`{}` {}", entry.text, message),
+                })
+            };
+
+            if secret.chars_to_guess() == 0 {
+                problem("has no characters to guess.".to_string());
+            }
+            if !secret.is_balanced() {
+                problem(format!(
+                    "has an odd number of `{}`, the last one is not closed.",
+                    CONF_LINE_SECRET_MODIFIER_VISIBLE
+                ));
+            }
+            if secret.longest_word() > LINE_WIDTH {
+                problem(format!(
+                    "has a word longer than {} characters, which is wider than the display.",
+                    LINE_WIDTH
+                ));
+            }
+            if !seen.insert(secret.to_plain_string()) {
+                problem("is a duplicate.".to_string());
+            }
+        }
+    }
+
+    problems
+}
+
+// ***********************
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let config = "secrets:\n- guess me\n- _good luck_\n- _good l_uck\n- hang_ man\n\
+                      - antidisestablishmentarianism\n\
+                      lessons:\n  animals:\n  - dog\n  - dog\n";
+        assert_eq!(
+            check(config),
+            [
+                "`_good luck_` has no characters to guess.",
+                "`_good l_uck` is a duplicate.",
+                "`hang_ man` has an odd number of `_`, the last one is not closed.",
+                "`antidisestablishmentarianism` has a word longer than 20 characters, \
+                 which is wider than the display.",
+                "Lesson `animals`: `dog` is a duplicate.",
+            ]
+        );
+
+        assert!(check("secrets:\n- guess me\n").is_empty());
+        // Escaped `_` are literal and need no closing `_`.
+        assert!(check("secrets:\n- snake\\_case\n- _snake\\_case_ too\n").is_empty());
+        assert_eq!(
+            check("secrets:\n- snake\\_case_\n"),
+            ["`snake\\_case_` has an odd number of `_`, the last one is not closed."]
+        );
+        assert!(check("secrets:\n- guess me\nlives: 0\n")[0].starts_with("Error in line 3"));
+
+        let image = format!(
+            "secrets:\n- guess me\nimage: |1\n  {}\n",
+            "\u{ff58}".repeat(33000)
+        );
+        let problems = check(&image);
+        assert!(problems[0].starts_with("Error in line 3, column 8:"));
+        assert!(problems[0].ends_with(
+            "The image size is 66001x1 characters, but at most 65535x65535 are allowed."
+        ));
+    }
+}
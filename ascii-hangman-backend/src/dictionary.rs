@@ -1,10 +1,19 @@
 //! This module deals with configuration data including the management of the list of secrets
 
 #![allow(clippy::manual_filter_map)]
+use crate::players::Players;
+use crate::secret::SecretConf;
+use crate::teams::Teams;
+use crate::{HINT_COST, LIVES, LIVES_MAX};
+use rand::distributions::WeightedIndex;
+use rand::seq::SliceRandom;
 use rand::Rng;
 use thiserror::Error;
 //use serde::Deserialize;
-use serde_derive::Deserialize;
+use serde_derive::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::io;
+use std::path::{Component, Path, PathBuf};
 
 /// A tag to enclose parts of the secret to be visible from the start, e.g.
 /// "guess_-me_" will be displayed in the game as "_ _ _ _ _ - m e"
@@ -14,6 +23,52 @@ pub const CONF_LINE_SECRET_MODIFIER_VISIBLE: char = '_';
 pub const CONF_LINE_SECRET_MODIFIER_LINEBREAK1: char = '\n';
 pub const CONF_LINE_SECRET_MODIFIER_LINEBREAK2: char = '|';
 
+/// The character following this tag is taken literally, e.g. "snake\\_case"
+/// is displayed as "s n a k e _ c a s e". Only `_`, `|` and the tag itself are escaped.
+pub const CONF_LINE_SECRET_MODIFIER_ESCAPE: char = '\\';
+
+/// Legacy format: a line starting with this character is a comment.
+pub const CONF_LINE_IDENTIFIER_COMMENT: char = '#';
+
+/// Legacy format: a line starting with this character is a game modifier.
+pub const CONF_LINE_IDENTIFIER_CONTROL: char = ':';
+
+/// Legacy format: a line starting with this character is a line of the ASCII-art image.
+pub const CONF_LINE_IDENTIFIER_IMAGE: char = '|';
+
+/// Legacy format: a line starting with this character is a secret string.
+pub const CONF_LINE_IDENTIFIER_SECRET: char = '-';
+
+/// Legacy format: game modifier equivalent to `traditional: true`.
+pub const CONF_LINE_MODIFIER_TRADITIONAL: &str = ":traditional-rewarding";
+
+/// Legacy format: game modifier equivalent to `traditional: false`.
+pub const CONF_LINE_MODIFIER_SUCCESS: &str = ":success-rewarding";
+
+/// Top level YAML keys. A line in the legacy format starting with one of them
+/// indicates, that the file is meant to be in YAML format.
+const YAML_KEYS: &[&str] = &[
+    "secrets",
+    "image",
+    "traditional",
+    "locale",
+    "ignore-accents",
+    "visible",
+    "visible-chars",
+    "alphabet",
+    "units",
+    "lives",
+    "hint-cost",
+    "players",
+    "random-turns",
+    "teams",
+    "timer",
+    "defeat",
+    "order",
+    "lessons",
+    "include",
+];
+
 // Custom error type used expressing potential syntax errors when parsing the configuration file.
 #[derive(Error, Debug)]
 pub enum ConfigParseError {
@@ -36,8 +91,26 @@ pub enum ConfigParseError {
     Edit config file and start again.\n"
     )]
     LineIdentifier { line_number: usize, line: String },
+    #[error["The number of `lives:` must be between 1 and {max}, but it is {lives}."]]
+    Lives { lives: i64, max: u8 },
+    #[error["The `hint-cost:` must be less than the `lives:`, so that one life is left \
+        after a hint, but it is {hint_cost} with {lives} lives."]]
+    HintCost { hint_cost: u8, lives: u8 },
+    #[error["The `timer:` must be at least 1 second, but it is {timer}."]]
+    Timer { timer: i64 },
+    #[error["There is no lesson named `{lesson}` in `lessons:`."]]
+    UnknownLesson { lesson: String },
+    #[error["Unknown `alphabet: {name}`. Choose `latin`, `greek`, `cyrillic` \
+    or list your own letters, e.g. `alphabet: {{custom: \"abcdefg\"}}`."]]
+    UnknownAlphabet { name: String },
     #[error["No image data found."]]
     NoImageData,
+    #[error["The image size is {width}x{height} characters, but at most {max}x{max} are allowed."]]
+    ImageTooBig {
+        width: usize,
+        height: usize,
+        max: u16,
+    },
     #[error["A config file must have a least one secret string, which is\n\
     a non-empty line starting with a letter, digit, '_' or '-'."]]
     NoSecretString,
@@ -63,6 +136,37 @@ pub enum ConfigParseError {
     NotInYamlFormat(#[from] serde_yaml::Error),
     #[error["No line: `secrets:` found (no spaces allowed before)."]]
     YamlSecretsLineMissing,
+    #[error["Can not read or write the saved game session:\n{0}"]]
+    SessionFormat(serde_yaml::Error),
+    #[error["The files `{first}` and `{second}` set different values for `{key}:`."]]
+    Conflict {
+        key: String,
+        first: String,
+        second: String,
+    },
+    #[error["Can not read `{file}`, included by `{including}`:\n{error}"]]
+    Include {
+        file: String,
+        including: String,
+        error: String,
+    },
+    #[error["The `include:` files form a cycle: {cycle}"]]
+    IncludeCycle { cycle: String },
+    #[error["Error in line {line}, column {column}:\n\n{excerpt}\n{suggestion}\n{error}"]]
+    Located {
+        line: usize,
+        column: usize,
+        /// The line with the error and a caret under the column.
+        excerpt: String,
+        /// How to fix common mistakes, followed by a newline. Can be empty.
+        suggestion: String,
+        error: Box<ConfigParseError>,
+    },
+    #[error["Error in `{file}`:\n{error}"]]
+    InFile {
+        file: String,
+        error: Box<ConfigParseError>,
+    },
 }
 
 /// We need this because `serde_yaml::Error` does not implement `PartialEq`.
@@ -74,44 +178,525 @@ impl PartialEq for ConfigParseError {
     }
 }
 
+impl ConfigParseError {
+    /// Adds the position of the error in `config`, the configuration it was
+    /// found in, and a suggestion how to fix common mistakes. Errors concerning
+    /// no line, e.g. `NoImageData`, `SessionFormat` or `NoSecretString` in an
+    /// empty file, are returned unchanged.
+    pub fn locate(self, config: &str) -> Self {
+        let config = config.trim_start_matches('\u{feff}');
+        let position = match &self {
+            ConfigParseError::GameModifier { line_number, .. }
+            | ConfigParseError::LineIdentifier { line_number, .. } => Some((*line_number, 1)),
+            ConfigParseError::Lives { .. } => key_position(config, "lives"),
+            ConfigParseError::HintCost { .. } => key_position(config, "hint-cost"),
+            ConfigParseError::Timer { .. } => key_position(config, "timer"),
+            ConfigParseError::Conflict { key, .. } => key_position(config, key),
+            ConfigParseError::NotInYamlFormat(e) => e.location().map(|l| (l.line(), l.column())),
+            ConfigParseError::NoSecretString => {
+                key_position(config, "secrets").or_else(|| key_position(config, "lessons"))
+            }
+            ConfigParseError::UnknownLesson { .. } => key_position(config, "lessons"),
+            ConfigParseError::UnknownAlphabet { .. } => key_position(config, "alphabet"),
+            ConfigParseError::ImageTooBig { .. } => key_position(config, "image"),
+            ConfigParseError::Include { file, .. } => include_position(config, file),
+            ConfigParseError::IncludeCycle { .. } => key_position(config, "include"),
+            // The first line, that is not in the legacy format.
+            ConfigParseError::YamlSecretsLineMissing => {
+                config.lines().enumerate().find_map(|(n, l)| {
+                    let indent = l.chars().take_while(|c| c.is_whitespace()).count();
+                    looks_like_yaml(l).then_some((n + 1, indent + 1))
+                })
+            }
+            ConfigParseError::NoImageData
+            | ConfigParseError::NotInProprietaryFormat
+            | ConfigParseError::SessionFormat(_)
+            | ConfigParseError::InFile { .. }
+            | ConfigParseError::Located { .. } => None,
+        };
+        let (line, column) = match position {
+            Some(p) => p,
+            None => return self,
+        };
+        let (line, column) = match &self {
+            // `serde_yaml` points to the start of the list, not to the entry.
+            ConfigParseError::NotInYamlFormat(e) if e.to_string().contains(SECRET_ERROR) => {
+                secret_position(config, line).unwrap_or((line, column))
+            }
+            _ => (line, column),
+        };
+        let text = match config.lines().nth(line.saturating_sub(1)) {
+            Some(text) => text,
+            None => return self,
+        };
+
+        // Keep the tabs, so that the caret stays under the column.
+        let indent: String = text
+            .chars()
+            .take(column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let gutter = " ".repeat(line.to_string().len());
+        let excerpt = format!("{} | {}\n{} | {}^", line, text, gutter, indent);
+        let suggestion = self
+            .suggestion(config, line, text)
+            .map(|s| s + "\n")
+            .unwrap_or_default();
+
+        ConfigParseError::Located {
+            line,
+            column,
+            excerpt,
+            suggestion,
+            error: Box::new(self),
+        }
+    }
+
+    /// How to fix common mistakes causing the error in the line `line`, with the content `text`.
+    fn suggestion(&self, config: &str, line: usize, text: &str) -> Option<String> {
+        if text.contains('\t') {
+            return Some("Indent with spaces, not with tabs.".to_string());
+        }
+
+        let e = match self {
+            ConfigParseError::NotInYamlFormat(e) => e.to_string(),
+            _ => return None,
+        };
+
+        let value = text.split_once(':').map(|(_, v)| v.trim().to_lowercase());
+        if e.contains("expected a boolean")
+            && value.is_some_and(|v| ["yes", "no", "on", "off"].contains(&v.as_str()))
+        {
+            return Some("Write `true` instead of `yes` and `false` instead of `no`.".to_string());
+        }
+
+        // Without `|1`, the ASCII-art is parsed as YAML.
+        let key_line = config
+            .lines()
+            .take(line)
+            .filter(|l| !l.starts_with([' ', '\t', '-', '#']) && l.contains(':'))
+            .last();
+        if key_line.is_some_and(|l| has_yaml_key(l, "image") && !l.contains('|')) {
+            return Some("Start the ASCII-art with `image: |1`.".to_string());
+        }
+
+        let secret = text.trim_start().strip_prefix('-')?.trim();
+        let keys = [
+            "text",
+            "hint",
+            "translation",
+            "category",
+            "difficulty",
+            "weight",
+        ];
+        if !secret.starts_with(['"', '\''])
+            && (secret.contains(": ") || secret.ends_with(':'))
+            && !keys.iter().any(|k| has_yaml_key(secret, k))
+        {
+            return Some(format!(
+                "Put secrets containing `: ` in quotes: `- \"{}\"`.",
+                secret.replace('"', "\\\"")
+            ));
+        }
+
+        None
+    }
+}
+
+/// Line and column of the first invalid entry of the list of secrets starting
+/// in line `line`. Only one line entries are checked.
+fn secret_position(config: &str, line: usize) -> Option<(usize, usize)> {
+    config
+        .lines()
+        .enumerate()
+        .skip(line.saturating_sub(1))
+        .take_while(|(n, l)| *n + 1 == line || l.starts_with([' ', '-', '#']) || l.is_empty())
+        .find_map(|(n, l)| {
+            let entry = l.trim_start().strip_prefix('-')?;
+            let column = l.chars().count() - entry.trim_start().chars().count() + 1;
+            serde_yaml::from_str::<SecretEntry>(entry)
+                .is_err()
+                .then_some((n + 1, column))
+        })
+}
+
+/// Line and column of the value of the top level key `key:`.
+fn key_position(config: &str, key: &str) -> Option<(usize, usize)> {
+    config.lines().enumerate().find_map(|(n, l)| {
+        let value = l.strip_prefix(key)?.strip_prefix(':')?;
+        let column =
+            key.chars().count() + 2 + value.chars().take_while(|c| c.is_whitespace()).count();
+        has_yaml_key(l, key).then_some((n + 1, column))
+    })
+}
+
+/// Line and column of the name of the file `file` in the `include:` list.
+fn include_position(config: &str, file: &str) -> Option<(usize, usize)> {
+    let (key_line, _) = key_position(config, "include")?;
+    let name = Path::new(file).file_name()?.to_str()?;
+    config
+        .lines()
+        .enumerate()
+        .skip(key_line - 1)
+        .find_map(|(n, l)| l.find(name).map(|i| (n + 1, l[..i].chars().count() + 1)))
+}
+
+/// One entry of the `secrets:` list: the secret string and optional information about it.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+#[serde(try_from = "RawSecretEntry")]
+pub struct SecretEntry {
+    /// The secret string to guess.
+    pub text: String,
+    /// Shown to the player when the round is over.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+    /// Shown to the player when the round is over.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub translation: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<u8>,
+    /// Relative probability to be chosen with `Order::Random`. Defaults to 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weight: Option<u32>,
+}
+
+/// Error message for `secrets:` list entries, that are neither a string nor a secret entry.
+const SECRET_ERROR: &str = "A secret must be a string or a mapping with a `text:` key.";
+
+/// A `secrets:` list entry is either a plain string or a mapping.
+/// Plain YAML numbers and booleans, e.g. `- 222`, are secrets too.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawSecretEntry {
+    Entry {
+        text: Value,
+        hint: Option<String>,
+        translation: Option<String>,
+        category: Option<String>,
+        difficulty: Option<u8>,
+        weight: Option<u32>,
+    },
+    Text(Value),
+}
+
+impl TryFrom<RawSecretEntry> for SecretEntry {
+    type Error = String;
+
+    fn try_from(raw: RawSecretEntry) -> Result<Self, Self::Error> {
+        let err = || SECRET_ERROR.to_string();
+        Ok(match raw {
+            RawSecretEntry::Text(text) => scalar_to_string(text).ok_or_else(err)?.into(),
+            RawSecretEntry::Entry {
+                text,
+                hint,
+                translation,
+                category,
+                difficulty,
+                weight,
+            } => Self {
+                text: scalar_to_string(text).ok_or_else(err)?,
+                hint,
+                translation,
+                category,
+                difficulty,
+                weight,
+            },
+        })
+    }
+}
+
+/// The string of a scalar YAML value, e.g. `222` for the number `222`.
+fn scalar_to_string(value: Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Named lists of secrets, in the order of the configuration file.
+#[derive(Debug, PartialEq, Clone, Default, Deserialize)]
+#[serde(try_from = "Mapping")]
+pub struct Lessons(Vec<(String, Vec<SecretEntry>)>);
+
+impl TryFrom<Mapping> for Lessons {
+    type Error = String;
+
+    fn try_from(mapping: Mapping) -> Result<Self, Self::Error> {
+        mapping
+            .into_iter()
+            .map(|(name, secrets)| {
+                let name = scalar_to_string(name)
+                    .ok_or_else(|| "A lesson name must be a string.".to_string())?;
+                let secrets = serde_yaml::from_value(secrets)
+                    .map_err(|e| format!("lesson `{}`: {}", name, e))?;
+                Ok((name, secrets))
+            })
+            .collect::<Result<_, String>>()
+            .map(Lessons)
+    }
+}
+
+impl From<String> for SecretEntry {
+    fn from(text: String) -> Self {
+        Self {
+            text,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&str> for SecretEntry {
+    fn from(text: &str) -> Self {
+        text.to_string().into()
+    }
+}
+
+/// The order in which the secrets are asked.
+#[derive(Debug, PartialEq, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Order {
+    /// Every time a secret is chosen randomly, taking into account its `weight`.
+    #[default]
+    Random,
+    /// The secrets are asked in the order of the configuration file.
+    Sequential,
+    /// The secrets are shuffled once when the session starts and then asked in this order.
+    ShuffleOnce,
+}
+
+impl Order {
+    /// Returns the index of the next secret to ask. `secrets` must not be empty.
+    pub fn select<R: Rng + ?Sized>(&self, secrets: &[SecretEntry], rng: &mut R) -> usize {
+        match self {
+            Order::Random => {
+                let weights = secrets.iter().map(|s| s.weight.unwrap_or(1));
+                match WeightedIndex::new(weights) {
+                    Ok(dist) => rng.sample(dist),
+                    // All weights are 0.
+                    Err(_) => rng.gen_range(0..secrets.len()),
+                }
+            }
+            Order::Sequential | Order::ShuffleOnce => 0,
+        }
+    }
+}
+
+/// What happens with a secret that was not guessed.
+/// In configuration files this is one of `requeue`, `requeue-last`, `drop`
+/// or the maximum number of times a secret is asked again.
+#[derive(Debug, PartialEq, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(try_from = "Value", into = "Value")]
+pub enum DefeatPolicy {
+    /// Ask the secret again, like any other remaining secret.
+    #[default]
+    Requeue,
+    /// Ask the secret again, after all other secrets.
+    RequeueLast,
+    /// Ask the secret again at most this many times, then drop it.
+    RequeueMax(u32),
+    /// Do not ask the secret again, but list it for review at the end of the session.
+    Drop,
+}
+
+impl TryFrom<Value> for DefeatPolicy {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match &value {
+            Value::String(s) if s == "requeue" => Ok(Self::Requeue),
+            Value::String(s) if s == "requeue-last" => Ok(Self::RequeueLast),
+            Value::String(s) if s == "drop" => Ok(Self::Drop),
+            Value::Number(n) => match n.as_u64().and_then(|n| u32::try_from(n).ok()) {
+                Some(n) => Ok(Self::RequeueMax(n)),
+                None => Err(format!("`defeat: {}` is not a positive number.", n)),
+            },
+            _ => Err(
+                "`defeat:` must be one of `requeue`, `requeue-last`, `drop` or a number."
+                    .to_string(),
+            ),
+        }
+    }
+}
+
+impl From<DefeatPolicy> for Value {
+    fn from(policy: DefeatPolicy) -> Self {
+        match policy {
+            DefeatPolicy::Requeue => Value::from("requeue"),
+            DefeatPolicy::RequeueLast => Value::from("requeue-last"),
+            DefeatPolicy::RequeueMax(n) => Value::from(n),
+            DefeatPolicy::Drop => Value::from("drop"),
+        }
+    }
+}
+
 /// A dictionary holding all secret sentences from among whom one is chosen randomly at the
 /// beginning of the game.
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, PartialEq, Default, Serialize, Deserialize)]
 pub struct Dict {
-    secrets: Vec<String>,
+    /// Optional, when there are `lessons`.
+    #[serde(default)]
+    secrets: Vec<SecretEntry>,
+    /// Named lists of secrets. The chosen lesson replaces `secrets`.
+    /// Not needed anymore, once the session has started.
+    #[serde(default, skip_serializing)]
+    lessons: Lessons,
+    /// Number of wrong guesses allowed. Defaults to `LIVES`.
+    lives: Option<i64>,
+    /// Number of lives a hint costs. Defaults to `HINT_COST`.
+    #[serde(rename = "hint-cost")]
+    hint_cost: Option<u8>,
+    /// Seconds allowed per guess. No time limit when missing.
+    timer: Option<i64>,
+    /// What happens with secrets that were not guessed. Defaults to `DefeatPolicy::Requeue`.
+    defeat: Option<DefeatPolicy>,
+    /// The order in which the secrets are asked. Defaults to `Order::Random`.
+    order: Option<Order>,
 }
 
 impl Dict {
-    /// Parse configuration file as toml data.
+    /// Parse configuration file as YAML data. When neither a `secrets:` nor a
+    /// `lessons:` line is found, fall back to the legacy line-based format.
+    /// Without `secrets:`, the first lesson is chosen.
     pub fn from(lines: &str) -> Result<Self, ConfigParseError> {
         // Trim BOM
         let lines = lines.trim_start_matches('\u{feff}');
 
-        if !lines
-            .lines()
-            .filter(|s| !s.trim_start().starts_with('#'))
-            .filter(|s| s.trim() != "")
-            .any(|s| s.trim_end() == "secrets:")
-        {
-            return Err(ConfigParseError::YamlSecretsLineMissing);
+        if !is_yaml_config(lines) {
+            return match LegacyConfig::from(lines) {
+                Ok(LegacyConfig { secrets, .. }) => Ok(Dict {
+                    secrets: secrets.into_iter().map(SecretEntry::from).collect(),
+                    ..Default::default()
+                }),
+                // This is an erroneous YAML file, not a legacy one.
+                Err(ConfigParseError::NotInProprietaryFormat) => {
+                    Err(ConfigParseError::YamlSecretsLineMissing)
+                }
+                Err(e) => Err(e),
+            };
         }
 
-        let dict: Dict = serde_yaml::from_str(lines)?;
+        let mut dict: Dict = serde_yaml::from_str(lines)?;
+        dict.check()?;
+
+        if dict.secrets.is_empty() {
+            if let Some((_, secrets)) = dict.lessons.0.first() {
+                dict.secrets = secrets.clone();
+            }
+        }
+        if dict.secrets.is_empty() {
+            return Err(ConfigParseError::NoSecretString);
+        }
 
         Ok(dict)
     }
 
-    /// Chooses randomly one secret from the dictionary and removes the secret from list
-    pub fn get_random_secret(&mut self) -> Option<String> {
-        match self.secrets.len() {
-            0 => None,
-            1 => Some(self.secrets.swap_remove(0)),
-            _ => {
-                let mut rng = rand::thread_rng();
-                let i = rng.gen_range(0..self.secrets.len());
-                Some(self.secrets.swap_remove(i))
+    /// Checks the ranges of the settings.
+    fn check(&self) -> Result<(), ConfigParseError> {
+        if let Some(lives) = self.lives {
+            if lives < 1 || lives > LIVES_MAX as i64 {
+                return Err(ConfigParseError::Lives {
+                    lives,
+                    max: LIVES_MAX,
+                });
+            }
+        }
+        if let Some(hint_cost) = self.hint_cost {
+            if hint_cost >= self.lives() {
+                return Err(ConfigParseError::HintCost {
+                    hint_cost,
+                    lives: self.lives(),
+                });
+            }
+        }
+        if let Some(timer) = self.timer {
+            if timer < 1 {
+                return Err(ConfigParseError::Timer { timer });
             }
         }
+        Ok(())
+    }
+
+    /// The names of the `lessons:`.
+    pub fn lessons(&self) -> Vec<String> {
+        self.lessons
+            .0
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// The `secrets:` and the secrets of every lesson, with the name of the lesson.
+    /// The `secrets:` are omitted, when they were taken from the first lesson.
+    pub fn secret_lists(&self) -> Vec<(Option<&str>, &[SecretEntry])> {
+        let mut lists = Vec::new();
+        if self.lessons.0.first().map(|(_, s)| s) != Some(&self.secrets) {
+            lists.push((None, &self.secrets[..]));
+        }
+        for (name, secrets) in &self.lessons.0 {
+            lists.push((Some(name.as_str()), &secrets[..]));
+        }
+        lists
+    }
+
+    /// Replaces the secrets with the ones of the lesson `name`.
+    pub fn select_lesson(&mut self, name: &str) -> Result<(), ConfigParseError> {
+        let (_, secrets) = self
+            .lessons
+            .0
+            .iter()
+            .find(|(n, _)| n == name)
+            .ok_or_else(|| ConfigParseError::UnknownLesson {
+                lesson: name.to_string(),
+            })?;
+        if secrets.is_empty() {
+            return Err(ConfigParseError::NoSecretString);
+        }
+        self.secrets = secrets.clone();
+        Ok(())
+    }
+
+    /// What happens with secrets that were not guessed.
+    pub fn defeat_policy(&self) -> DefeatPolicy {
+        self.defeat.unwrap_or_default()
+    }
+
+    /// Seconds allowed per guess, if there is a time limit.
+    pub fn timer(&self) -> Option<u64> {
+        // `Dict::from()` guarantees the range.
+        self.timer.map(|t| t as u64)
+    }
+
+    /// Number of lives a hint costs.
+    pub fn hint_cost(&self) -> u8 {
+        self.hint_cost.unwrap_or(HINT_COST)
+    }
+
+    /// Number of wrong guesses allowed per game.
+    pub fn lives(&self) -> u8 {
+        // `Dict::from()` guarantees the range.
+        self.lives.map_or(LIVES, |l| l as u8)
+    }
+
+    /// Shuffles the secrets, when they are asked in `Order::ShuffleOnce`.
+    /// Call this once, before the first secret is chosen.
+    pub fn shuffle_once<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        if self.order == Some(Order::ShuffleOnce) {
+            self.secrets.shuffle(rng);
+        }
+    }
+
+    /// Chooses one secret from the dictionary according to the configured `Order`
+    /// and removes the secret from list. The order of the remaining secrets is kept.
+    pub fn next_secret<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Option<SecretEntry> {
+        if self.secrets.is_empty() {
+            return None;
+        }
+        let i = self.order.unwrap_or_default().select(&self.secrets, rng);
+        Some(self.secrets.remove(i))
     }
 
     /// Is there exactly one secret left?
@@ -120,17 +705,371 @@ impl Dict {
     }
 
     /// Add a secret to the list.
-    pub fn add(&mut self, secret: String) {
+    pub fn add(&mut self, secret: SecretEntry) {
         self.secrets.push(secret);
     }
 }
 
+/// Merges several configurations, each given with the name of its file, into
+/// one YAML configuration. The `secrets:` and the `lessons:` are united, the
+/// last `image:` wins and all other settings must not contradict each other.
+/// The `include:` lists are dropped, see `resolve_includes()`.
+/// Empty configurations are ignored and a single one is returned unchanged.
+pub fn merge_configs(sources: &[(&str, &str)]) -> Result<String, ConfigParseError> {
+    let sources: Vec<_> = sources
+        .iter()
+        .filter(|(_, config)| !config.trim_start_matches('\u{feff}').trim().is_empty())
+        .collect();
+    if let [(_, config)] = sources[..] {
+        return Ok(config.to_string());
+    }
+
+    let mut merged = Mapping::new();
+    // The file every setting comes from.
+    let mut origin: Vec<(Value, &str)> = Vec::new();
+    for (file, config) in sources {
+        let in_file = |error| ConfigParseError::InFile {
+            file: file.to_string(),
+            error: Box::new(error),
+        };
+        let mapping = config_to_mapping(config).map_err(|e| in_file(e.locate(config)))?;
+
+        for (key, value) in mapping {
+            match (key.as_str(), merged.get_mut(&key), value) {
+                (Some("include"), _, _) => {}
+                (Some("secrets"), Some(Value::Sequence(old)), Value::Sequence(new)) => {
+                    unite(old, new)
+                }
+                (Some("lessons"), Some(Value::Mapping(old)), Value::Mapping(new)) => {
+                    for (name, new) in new {
+                        match (old.get_mut(&name), new) {
+                            (Some(Value::Sequence(old)), Value::Sequence(new)) => unite(old, new),
+                            (_, new) => {
+                                old.insert(name, new);
+                            }
+                        }
+                    }
+                }
+                (Some("image"), _, value) | (_, None, value) => {
+                    origin.push((key.clone(), file));
+                    merged.insert(key, value);
+                }
+                (_, Some(old), value) => {
+                    if *old != value {
+                        let first = origin.iter().find(|(k, _)| *k == key).map(|(_, f)| *f);
+                        let conflict = ConfigParseError::Conflict {
+                            key: key.as_str().unwrap_or_default().to_string(),
+                            first: first.unwrap_or_default().to_string(),
+                            second: file.to_string(),
+                        };
+                        return Err(in_file(conflict.locate(config)));
+                    }
+                }
+            }
+        }
+    }
+
+    if merged.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(serde_yaml::to_string(&merged)?)
+}
+
+/// Reads the files named in the `include:` list of the configuration `config`
+/// of the file `file`, and recursively the files they include. Relative names
+/// are relative to the including file. `read` returns the content of a file.
+/// The result lists every file once, the included before the including ones,
+/// ready for `merge_configs()`.
+pub fn resolve_includes<F>(
+    file: &str,
+    config: &str,
+    read: &mut F,
+) -> Result<Vec<(String, String)>, ConfigParseError>
+where
+    F: FnMut(&Path) -> io::Result<String>,
+{
+    let mut sources = Vec::new();
+    include(
+        Path::new(file),
+        config.to_string(),
+        read,
+        &mut Vec::new(),
+        &mut sources,
+    )?;
+    Ok(sources)
+}
+
+/// Adds `config` read from `file` and its includes to `sources`. `stack`
+/// holds the chain of including files, to detect cycles.
+fn include<F>(
+    file: &Path,
+    config: String,
+    read: &mut F,
+    stack: &mut Vec<PathBuf>,
+    sources: &mut Vec<(String, String)>,
+) -> Result<(), ConfigParseError>
+where
+    F: FnMut(&Path) -> io::Result<String>,
+{
+    let path = normalize(file);
+    let name = path.display().to_string();
+    let includes = includes(&config).map_err(|error| ConfigParseError::InFile {
+        file: name.clone(),
+        error: Box::new(error.locate(&config)),
+    })?;
+
+    stack.push(path.clone());
+    for included in includes {
+        let included = normalize(
+            &path
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join(included),
+        );
+        if let Some(i) = stack.iter().position(|p| *p == included) {
+            let cycle: Vec<String> = stack[i..]
+                .iter()
+                .chain([&included])
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(ConfigParseError::IncludeCycle {
+                cycle: cycle.join(" -> "),
+            });
+        }
+        // Every file is read only once.
+        if sources.iter().any(|(f, _)| Path::new(f) == included) {
+            continue;
+        }
+        let config = read(&included).map_err(|e| {
+            ConfigParseError::Include {
+                file: included.display().to_string(),
+                including: name.clone(),
+                error: e.to_string(),
+            }
+            .locate(&config)
+        })?;
+        include(&included, config, read, stack, sources)?;
+    }
+    stack.pop();
+
+    sources.push((name, config));
+    Ok(())
+}
+
+/// The file names in the `include:` list, which may also be a single name.
+fn includes(config: &str) -> Result<Vec<String>, ConfigParseError> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawNames {
+        One(String),
+        Many(Vec<String>),
+    }
+    #[derive(Deserialize)]
+    struct RawInclude {
+        include: Option<RawNames>,
+    }
+
+    let config = config.trim_start_matches('\u{feff}');
+    if !has_yaml_key(config, "include") {
+        return Ok(Vec::new());
+    }
+
+    let raw: RawInclude = serde_yaml::from_str(config)?;
+    Ok(match raw.include {
+        None => Vec::new(),
+        Some(RawNames::One(name)) => vec![name],
+        Some(RawNames::Many(names)) => names,
+    })
+}
+
+/// Removes `.` and `..` from the path, without asking the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+/// Reads a configuration in YAML or in the legacy format and checks its settings.
+fn config_to_mapping(config: &str) -> Result<Mapping, ConfigParseError> {
+    let config = config.trim_start_matches('\u{feff}');
+
+    let mapping = match LegacyConfig::from(config) {
+        Ok(LegacyConfig {
+            secrets,
+            image,
+            traditional,
+        }) => {
+            let mut mapping = Mapping::new();
+            if !secrets.is_empty() {
+                mapping.insert("secrets".into(), secrets.into());
+            }
+            if let Some(image) = image {
+                mapping.insert("image".into(), image.into());
+            }
+            if let Some(traditional) = traditional {
+                mapping.insert("traditional".into(), traditional.into());
+            }
+            mapping
+        }
+        Err(ConfigParseError::NotInProprietaryFormat) => serde_yaml::from_str(config)?,
+        Err(e) => return Err(e),
+    };
+
+    serde_yaml::from_value::<Dict>(Value::Mapping(mapping.clone()))?.check()?;
+    // The other settings are read from the text, so that errors point into it.
+    SecretConf::from_yaml(config)?;
+    Players::from_yaml(config)?;
+    Teams::from_yaml(config)?;
+
+    Ok(mapping)
+}
+
+/// Appends the entries of `new` missing in `old`.
+fn unite(old: &mut Vec<Value>, new: Vec<Value>) {
+    for entry in new {
+        if !old.contains(&entry) {
+            old.push(entry);
+        }
+    }
+}
+
+/// Is the configuration in YAML format? YAML configurations have a `secrets:`
+/// or a `lessons:` line, otherwise they are in the legacy line-based format.
+pub fn is_yaml_config(lines: &str) -> bool {
+    has_yaml_key(lines, "secrets") || has_yaml_key(lines, "lessons")
+}
+
+/// Is there a line starting with the top level YAML key `key:`?
+/// Comment lines are ignored.
+pub fn has_yaml_key(lines: &str, key: &str) -> bool {
+    lines
+        .lines()
+        .filter(|s| !s.trim_start().starts_with('#'))
+        .any(|s| {
+            s.strip_prefix(key)
+                .and_then(|s| s.strip_prefix(':'))
+                .is_some_and(|s| s.is_empty() || s.starts_with(char::is_whitespace))
+        })
+}
+
+/// Configuration data in the legacy line-based format, that was used before
+/// the YAML format was introduced.
+#[derive(Debug, PartialEq, Default)]
+pub struct LegacyConfig {
+    pub secrets: Vec<String>,
+    pub image: Option<String>,
+    pub traditional: Option<bool>,
+}
+
+impl LegacyConfig {
+    /// Parse configuration file data in the legacy format:
+    /// every non-empty line is either a comment, a secret, a line of the
+    /// ASCII-art image or a game modifier.
+    pub fn from(lines: &str) -> Result<Self, ConfigParseError> {
+        // Trim BOM
+        let lines = lines.trim_start_matches('\u{feff}');
+
+        if lines
+            .lines()
+            .filter(|l| !l.trim_start().starts_with(CONF_LINE_IDENTIFIER_COMMENT))
+            .any(looks_like_yaml)
+        {
+            return Err(ConfigParseError::NotInProprietaryFormat);
+        }
+
+        let mut config = LegacyConfig::default();
+        let mut image = String::new();
+
+        for (n, line) in lines.lines().enumerate() {
+            let line = line.trim_end();
+            let line_number = n + 1;
+
+            match line.chars().next() {
+                None | Some(CONF_LINE_IDENTIFIER_COMMENT) => {}
+                Some(CONF_LINE_IDENTIFIER_SECRET) => {
+                    let secret = line[1..].trim_start();
+                    if !secret.is_empty() {
+                        config.secrets.push(secret.to_string());
+                    }
+                }
+                Some(CONF_LINE_IDENTIFIER_IMAGE) => {
+                    image.push_str(&line[1..]);
+                    image.push('\n');
+                }
+                Some(CONF_LINE_IDENTIFIER_CONTROL) => {
+                    config.traditional = match line {
+                        CONF_LINE_MODIFIER_TRADITIONAL => Some(true),
+                        CONF_LINE_MODIFIER_SUCCESS => Some(false),
+                        _ => {
+                            return Err(ConfigParseError::GameModifier {
+                                line_number,
+                                line: line.to_string(),
+                            })
+                        }
+                    };
+                }
+                Some(c)
+                    if c.is_alphanumeric()
+                        || c == CONF_LINE_SECRET_MODIFIER_VISIBLE
+                        || c == CONF_LINE_SECRET_MODIFIER_ESCAPE =>
+                {
+                    config.secrets.push(line.to_string());
+                }
+                Some(_) => {
+                    return Err(ConfigParseError::LineIdentifier {
+                        line_number,
+                        line: line.to_string(),
+                    })
+                }
+            }
+        }
+
+        if config.secrets.is_empty() {
+            return Err(ConfigParseError::NoSecretString);
+        }
+
+        if !image.is_empty() {
+            config.image = Some(image);
+        }
+
+        Ok(config)
+    }
+}
+
+/// Does this line look like a top level YAML key, e.g. `image: |1` or `secrets:`?
+/// A `secrets:` key with extra letters, e.g. `sxxxecrets:`, counts too, but
+/// other words ending with `:`, e.g. the secrets `Achtung:` or `borders:`, do not.
+fn looks_like_yaml(line: &str) -> bool {
+    let line = line.trim();
+    YAML_KEYS.iter().any(|k| has_yaml_key(line, k))
+        || line.strip_suffix(':').is_some_and(|word| {
+            word.starts_with('s')
+                && word.ends_with("ecrets")
+                && word.chars().all(|c| c.is_ascii_lowercase())
+        })
+}
+
 // ***********************
 
 #[cfg(test)]
 mod tests {
     use super::ConfigParseError;
     use super::Dict;
+    use super::LegacyConfig;
+    use super::SecretEntry;
 
     /// parse all 3 data types in configuration file format
     #[test]
@@ -144,44 +1083,481 @@ secrets:
 
 traditional: true
 ";
-        let dict = Dict::from(&config).unwrap();
+        let dict = Dict::from(config).unwrap();
 
         let expected = Dict {
-            secrets: vec![
-                "guess me".to_string(),
-                "hang_man_".to_string(),
-                "_good l_uck".to_string(),
-            ],
+            secrets: vec!["guess me".into(), "hang_man_".into(), "_good l_uck".into()],
+            ..Default::default()
         };
 
         assert_eq!(dict, expected);
         let config = "# comment\nsecrets:\n  - guess me\n";
-        let dict = Dict::from(&config);
+        let dict = Dict::from(config);
         let expected = Ok(Dict {
-            secrets: vec!["guess me".to_string()],
+            secrets: vec!["guess me".into()],
+            ..Default::default()
         });
         assert_eq!(dict, expected);
 
         let config = "# comment\nsecrets:\n- guess me\n";
-        let dict = Dict::from(&config);
+        let dict = Dict::from(config);
         let expected = Ok(Dict {
-            secrets: vec!["guess me".to_string()],
+            secrets: vec!["guess me".into()],
+            ..Default::default()
         });
         assert_eq!(dict, expected);
 
         let config = "# comment\nsecrets:\n- 222\n";
-        let dict = Dict::from(&config);
+        let dict = Dict::from(config);
         let expected = Ok(Dict {
-            secrets: vec!["222".to_string()],
+            secrets: vec!["222".into()],
+            ..Default::default()
         });
         assert_eq!(dict, expected);
 
         let config = "sxxxecrets:";
-        let dict = Dict::from(&config).unwrap_err();
+        let dict = Dict::from(config).unwrap_err();
         assert!(matches!(dict, ConfigParseError::YamlSecretsLineMissing));
 
         let config = "# comment\nsecrets:\n   guess me\n";
-        let dict = Dict::from(&config).unwrap_err();
+        let dict = Dict::from(config).unwrap_err();
+        assert!(matches!(dict, ConfigParseError::NotInYamlFormat(_)));
+
+        let config = "# comment\nguess me\n- hang_man_\n";
+        let dict = Dict::from(config);
+        let expected = Ok(Dict {
+            secrets: vec!["guess me".into(), "hang_man_".into()],
+            ..Default::default()
+        });
+        assert_eq!(dict, expected);
+
+        let config = "# comment\n secrets:\n- guess me\n";
+        let dict = Dict::from(config).unwrap_err();
+        assert!(matches!(dict, ConfigParseError::YamlSecretsLineMissing));
+    }
+
+    /// parse secrets given as plain strings and as mappings
+    #[test]
+    fn test_from_secret_entries() {
+        let config = "
+secrets:
+- guess me
+- text: der Hund
+  hint: an animal
+  translation: the dog
+  category: animals
+  difficulty: 2
+- text: die Katze
+";
+        let dict = Dict::from(config).unwrap();
+        let expected = Dict {
+            secrets: vec![
+                "guess me".into(),
+                SecretEntry {
+                    text: "der Hund".to_string(),
+                    hint: Some("an animal".to_string()),
+                    translation: Some("the dog".to_string()),
+                    category: Some("animals".to_string()),
+                    difficulty: Some(2),
+                    weight: None,
+                },
+                "die Katze".into(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(dict, expected);
+
+        let config = "secrets:\n- hint: no text\n";
+        let dict = Dict::from(config).unwrap_err();
         assert!(matches!(dict, ConfigParseError::NotInYamlFormat(_)));
     }
+
+    /// ask secrets in the configured order
+    #[test]
+    fn test_next_secret() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut play = |config: &str| {
+            let mut dict = Dict::from(config).unwrap();
+            dict.shuffle_once(&mut rng);
+            let mut played = Vec::new();
+            while let Some(entry) = dict.next_secret(&mut rng) {
+                played.push(entry.text);
+            }
+            played
+        };
+
+        let secrets = "secrets:\n- a\n- b\n- c\n- d\n- e\n- f\n- g\n- h\n";
+        let in_order: Vec<String> = "abcdefgh".chars().map(|c| c.to_string()).collect();
+        assert_eq!(play(&format!("{}order: sequential\n", secrets)), in_order);
+
+        let mut shuffled = play(&format!("{}order: shuffle-once\n", secrets));
+        assert_ne!(shuffled, in_order);
+        shuffled.sort();
+        assert_eq!(shuffled, in_order);
+
+        let mut random = play(&format!("{}order: random\n", secrets));
+        random.sort();
+        assert_eq!(random, in_order);
+
+        // Weight 0 secrets come last.
+        let config = "secrets:\n- text: a\n  weight: 0\n- b\n- text: c\n  weight: 5\n";
+        assert_eq!(play(config).last().unwrap(), "a");
+
+        let config = "secrets:\n- a\norder: alphabetic\n";
+        assert!(Dict::from(config).is_err());
+    }
+
+    /// parse named lists of secrets
+    #[test]
+    fn test_from_lessons() {
+        let config = "
+lessons:
+  animals:
+  - der Hund
+  - text: die Katze
+    hint: miau
+  2:
+  - eins
+  - zwei
+";
+        let mut dict = Dict::from(config).unwrap();
+        assert_eq!(dict.lessons(), vec!["animals", "2"]);
+        // The first lesson is the default.
+        assert_eq!(dict.secrets.len(), 2);
+        assert_eq!(dict.secrets[0].text, "der Hund");
+
+        dict.select_lesson("2").unwrap();
+        assert_eq!(dict.secrets, vec!["eins".into(), "zwei".into()]);
+
+        let err = dict.select_lesson("colours").unwrap_err();
+        assert!(matches!(err, ConfigParseError::UnknownLesson { .. }));
+
+        // `secrets:` is optional with lessons only.
+        let config = "secrets: []\n";
+        let err = Dict::from(config).unwrap_err();
+        assert!(matches!(err, ConfigParseError::NoSecretString));
+
+        let config = "lessons:\n  animals: [der Hund]\n  empty: []\n";
+        let mut dict = Dict::from(config).unwrap();
+        let err = dict.select_lesson("empty").unwrap_err();
+        assert!(matches!(err, ConfigParseError::NoSecretString));
+
+        let config = "lessons:\n  animals: der Hund\n";
+        let err = Dict::from(config).unwrap_err();
+        assert!(matches!(err, ConfigParseError::NotInYamlFormat(_)));
+    }
+
+    /// parse the defeat policy
+    #[test]
+    fn test_from_defeat() {
+        use super::DefeatPolicy;
+        let policy = |p: &str| {
+            Dict::from(&format!("secrets:\n- guess me\ndefeat: {}\n", p)).map(|d| d.defeat_policy())
+        };
+        assert_eq!(policy("requeue"), Ok(DefeatPolicy::Requeue));
+        assert_eq!(policy("requeue-last"), Ok(DefeatPolicy::RequeueLast));
+        assert_eq!(policy("2"), Ok(DefeatPolicy::RequeueMax(2)));
+        assert_eq!(policy("drop"), Ok(DefeatPolicy::Drop));
+        assert!(policy("-1").is_err());
+        assert!(policy("never").is_err());
+        assert_eq!(Dict::default().defeat_policy(), DefeatPolicy::Requeue);
+    }
+
+    /// parse and check the number of lives
+    #[test]
+    fn test_from_lives() {
+        let config = "secrets:\n- guess me\n";
+        assert_eq!(Dict::from(config).unwrap().lives(), crate::LIVES);
+
+        let config = "secrets:\n- guess me\nlives: 10\n";
+        assert_eq!(Dict::from(config).unwrap().lives(), 10);
+
+        let config = "secrets:\n- guess me\nlives: 0\n";
+        let dict = Dict::from(config).unwrap_err();
+        assert!(matches!(dict, ConfigParseError::Lives { lives: 0, .. }));
+
+        let config = "secrets:\n- guess me\nlives: 5\nhint-cost: 2\n";
+        let dict = Dict::from(config).unwrap();
+        assert_eq!(dict.lives(), 5);
+        assert_eq!(dict.hint_cost(), 2);
+        assert_eq!(Dict::default().hint_cost(), crate::HINT_COST);
+
+        let config = "secrets:\n- guess me\nlives: 3\nhint-cost: 3\n";
+        let dict = Dict::from(config).unwrap_err();
+        assert!(matches!(
+            dict,
+            ConfigParseError::HintCost {
+                hint_cost: 3,
+                lives: 3
+            }
+        ));
+        let config = "secrets:\n- guess me\nhint-cost: 7\n";
+        assert!(Dict::from(config).is_err());
+
+        let config = "secrets:\n- guess me\ntimer: 10\n";
+        assert_eq!(Dict::from(config).unwrap().timer(), Some(10));
+        assert_eq!(Dict::default().timer(), None);
+
+        let config = "secrets:\n- guess me\ntimer: 0\n";
+        let dict = Dict::from(config).unwrap_err();
+        assert!(matches!(dict, ConfigParseError::Timer { timer: 0 }));
+
+        let config = "secrets:\n- guess me\nlives: 1000\n";
+        let dict = Dict::from(config).unwrap_err();
+        assert!(matches!(dict, ConfigParseError::Lives { lives: 1000, .. }));
+    }
+
+    /// parse all line types of the legacy configuration file format
+    #[test]
+    fn test_legacy_from() {
+        let config: &str = "
+# comment
+guess me
+- hang_man_
+-   _good l_uck
+\u{c4}pfel
+:traditional-rewarding
+
+|  ::
+|C|__|
+";
+        let config = LegacyConfig::from(config).unwrap();
+
+        let expected = LegacyConfig {
+            secrets: vec![
+                "guess me".to_string(),
+                "hang_man_".to_string(),
+                "_good l_uck".to_string(),
+                "\u{c4}pfel".to_string(),
+            ],
+            image: Some("  ::\nC|__|\n".to_string()),
+            traditional: Some(true),
+        };
+        assert_eq!(config, expected);
+
+        let config = "guess me\n:success-rewarding\n";
+        let config = LegacyConfig::from(config).unwrap();
+        assert_eq!(config.traditional, Some(false));
+        assert_eq!(config.image, None);
+
+        let config = "guess me\n\n:unknown-rewarding\n";
+        let err = LegacyConfig::from(config).unwrap_err();
+        assert_eq!(
+            err,
+            ConfigParseError::GameModifier {
+                line_number: 3,
+                line: ":unknown-rewarding".to_string()
+            }
+        );
+
+        let config = "guess me\n  indented\n";
+        let err = LegacyConfig::from(config).unwrap_err();
+        assert_eq!(
+            err,
+            ConfigParseError::LineIdentifier {
+                line_number: 2,
+                line: "  indented".to_string()
+            }
+        );
+
+        let config = "# only comments\n|  ::\n";
+        let err = LegacyConfig::from(config).unwrap_err();
+        assert!(matches!(err, ConfigParseError::NoSecretString));
+
+        let config = "guess me\nimage: |1\n  ::\n";
+        let err = LegacyConfig::from(config).unwrap_err();
+        assert!(matches!(err, ConfigParseError::NotInProprietaryFormat));
+
+        // Only known keys make a line look like YAML.
+        let config = LegacyConfig::from("Achtung:\nguess me\n").unwrap();
+        assert_eq!(config.secrets, vec!["Achtung:", "guess me"]);
+        let dict = Dict::from("borders:\nStimmer:\n").unwrap();
+        assert_eq!(dict.secrets, vec!["borders:".into(), "Stimmer:".into()]);
+    }
+
+    #[test]
+    fn test_merge_configs() {
+        use super::merge_configs;
+
+        let a = "secrets:\n- guess me\n- hang man\ntraditional: true\nimage: |1\n  ::\n";
+        let b = "- hang man\n- good luck\n|  ||\n:traditional-rewarding\n";
+        let merged = merge_configs(&[("a.txt", a), ("b.txt", b)]).unwrap();
+        let dict = Dict::from(&merged).unwrap();
+        let expected = Dict {
+            secrets: vec!["guess me".into(), "hang man".into(), "good luck".into()],
+            ..Default::default()
+        };
+        assert_eq!(dict, expected);
+        assert!(merged.contains("image: \"  ||\\n\""));
+
+        let b = "secrets:\n- good luck\ntraditional: false\n";
+        let err = merge_configs(&[("a.txt", a), ("b.txt", b)]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Error in `b.txt`:\n\
+             Error in line 3, column 14:\n\n\
+             3 | traditional: false\n  |              ^\n\n\
+             The files `a.txt` and `b.txt` set different values for `traditional:`."
+        );
+
+        let b = "secrets:\n- good luck\nlives: 0\n";
+        let err = merge_configs(&[("a.txt", a), ("b.txt", b)]).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Error in `b.txt`:\nError in line 3, column 8:"));
+
+        // All settings are checked in the file they are written in.
+        let c = "# Settings only\nteams: [Red]\n\nignore-accents: maybe\n";
+        let err = merge_configs(&[("a.txt", a), ("c.txt", c)]).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Error in `c.txt`:\nError in line 4, column 17:"));
+        let c = "players: Anna\n";
+        let err = merge_configs(&[("a.txt", a), ("c.txt", c)]).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Error in `c.txt`:\nError in line 1"));
+
+        // A single configuration is not altered.
+        let merged = merge_configs(&[("a.txt", a), ("b.txt", " \n")]).unwrap();
+        assert_eq!(merged, a);
+    }
+
+    #[test]
+    fn test_resolve_includes() {
+        use super::{merge_configs, resolve_includes};
+        use std::collections::HashMap;
+        use std::io;
+        use std::path::Path;
+
+        let files: HashMap<&str, &str> = [
+            (
+                "shared/animals.yaml",
+                "include: ../common.yaml\nsecrets:\n- dog\n",
+            ),
+            ("common.yaml", "secrets:\n- cat\n"),
+            ("shared/loop.yaml", "include: [./../lesson.yaml]\n"),
+        ]
+        .into_iter()
+        .collect();
+        let mut read = |path: &Path| {
+            files
+                .get(path.to_str().unwrap())
+                .map(|s| s.to_string())
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        };
+
+        let lesson = "include:\n- shared/animals.yaml\n- common.yaml\nsecrets:\n- bird\n";
+        let sources = resolve_includes("lesson.yaml", lesson, &mut read).unwrap();
+        let names: Vec<&str> = sources.iter().map(|(f, _)| f.as_str()).collect();
+        assert_eq!(names, ["common.yaml", "shared/animals.yaml", "lesson.yaml"]);
+
+        let sources: Vec<(&str, &str)> = sources
+            .iter()
+            .map(|(f, c)| (f.as_str(), c.as_str()))
+            .collect();
+        let dict = Dict::from(&merge_configs(&sources).unwrap()).unwrap();
+        assert_eq!(dict.secrets, ["cat".into(), "dog".into(), "bird".into()]);
+
+        let lesson = "include: shared/loop.yaml\nsecrets:\n- bird\n";
+        let err = resolve_includes("lesson.yaml", lesson, &mut read).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The `include:` files form a cycle: \
+             lesson.yaml -> shared/loop.yaml -> lesson.yaml"
+        );
+
+        let lesson = "include: missing.yaml\nsecrets:\n- bird\n";
+        let err = resolve_includes("lesson.yaml", lesson, &mut read).unwrap_err();
+        match err {
+            ConfigParseError::Located {
+                line,
+                column,
+                error,
+                ..
+            } => {
+                assert_eq!((line, column), (1, 10));
+                assert!(matches!(*error, ConfigParseError::Include { .. }));
+            }
+            e => panic!("not located: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_locate() {
+        let locate = |config: &str| {
+            let err = Dict::from(config).unwrap_err().locate(config);
+            match err {
+                ConfigParseError::Located {
+                    line,
+                    column,
+                    excerpt,
+                    suggestion,
+                    ..
+                } => (line, column, excerpt, suggestion),
+                e => panic!("not located: {:?}", e),
+            }
+        };
+
+        let (line, column, excerpt, suggestion) =
+            locate("secrets:\n- guess me\n- der Hund: the dog\n");
+        assert_eq!((line, column), (3, 3));
+        assert_eq!(excerpt, "3 | - der Hund: the dog\n  |   ^");
+        assert_eq!(
+            suggestion,
+            "Put secrets containing `: ` in quotes: `- \"der Hund: the dog\"`.\n"
+        );
+
+        let (line, _, excerpt, suggestion) = locate("secrets:\n\t- guess me\n");
+        assert_eq!(line, 2);
+        assert_eq!(excerpt, "2 | \t- guess me\n  | ^");
+        assert_eq!(suggestion, "Indent with spaces, not with tabs.\n");
+
+        let (line, column, _, suggestion) = locate("secrets:\n- guess me\nlives: no\n");
+        assert_eq!((line, column), (3, 8));
+        assert_eq!(suggestion, "");
+
+        let (line, _, _, suggestion) = locate("secrets:\n- guess me\nimage:\n   ::\n  C|__|\n");
+        assert_eq!(line, 5);
+        assert_eq!(suggestion, "Start the ASCII-art with `image: |1`.\n");
+
+        let (line, column, _, _) = locate("guess me\n\n?x\n");
+        assert_eq!((line, column), (3, 1));
+
+        let (line, column, _, _) = locate("secrets: []\n");
+        assert_eq!((line, column), (1, 10));
+
+        let (line, column, _, _) = locate("# Animals\n secrets:\n - dog\n");
+        assert_eq!((line, column), (2, 2));
+
+        let config = "lessons:\n  animals: [dog]\n";
+        let mut dict = Dict::from(config).unwrap();
+        let err = dict.select_lesson("plants").unwrap_err().locate(config);
+        assert!(matches!(err, ConfigParseError::Located { line: 1, .. }));
+
+        let config = "secrets: [dog]\ninclude:\n- shared/plants.yaml\n";
+        let err = ConfigParseError::Include {
+            file: "shared/plants.yaml".to_string(),
+            including: "a.yaml".to_string(),
+            error: "not found".to_string(),
+        }
+        .locate(config);
+        assert!(matches!(
+            err,
+            ConfigParseError::Located {
+                line: 3,
+                column: 10,
+                ..
+            }
+        ));
+
+        let config = "secrets: [dog]\nlives: 2\nhint-cost: 2\n";
+        let err = Dict::from(config).unwrap_err().locate(config);
+        assert!(matches!(err, ConfigParseError::Located { line: 3, .. }));
+
+        let err = Dict::from("").unwrap_err().locate("");
+        assert_eq!(err, ConfigParseError::NoSecretString);
+    }
 }
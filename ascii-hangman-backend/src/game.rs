@@ -1,9 +1,13 @@
 //!Defines the game state and logic
 use crate::secret::Secret;
+use crate::secret::SecretConf;
+use rand::Rng;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 use std::fmt;
 
 /// A subset of the game state. Can be derived from `Game` struct.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum State {
     /// The game is ongoing.
     Ongoing,
@@ -17,43 +21,145 @@ pub enum State {
     DefeatGameOver,
 }
 
+/// The outcome of a guess.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum GuessOutcome {
+    /// The guessed character is part of the secret.
+    Correct,
+    /// The guessed character is not part of the secret. This costs a life.
+    Missed,
+    /// The character was guessed before. This costs nothing.
+    Repeated,
+    /// The character was disclosed by a hint.
+    Hint,
+    /// A hint was requested, but there are not enough lives left.
+    HintRefused,
+    /// No guess was made in time. This costs a life.
+    Timeout,
+    /// The character is not part of the alphabet. This costs nothing.
+    Rejected,
+}
+
 /// The game state.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Game {
     pub secret: Secret,
     pub lifes: u8,
-    pub last_guess: char,
+    /// Number of lives at the beginning of the game.
+    pub max_lifes: u8,
+    pub last_guess: String,
+    /// Outcome of `last_guess`; `None` when nothing was guessed yet.
+    pub last_outcome: Option<GuessOutcome>,
+    /// Guessed characters found in the secret.
+    pub correct_guesses: BTreeSet<String>,
+    /// Guessed characters not found in the secret.
+    pub missed_guesses: BTreeSet<String>,
+    /// Number of hidden characters disclosed by guesses and hints.
+    pub revealed: usize,
+    /// Number of hidden characters disclosed by hints; included in `revealed`.
+    #[serde(default)]
+    pub hinted: usize,
     pub state: State,
     pub last_game: bool,
 }
 
 impl Game {
     /// Constructor.
-    pub fn new(secretstr: &str, lifes: u8, last_game: bool) -> Self {
+    pub fn new(secretstr: &str, conf: &SecretConf, lifes: u8, last_game: bool) -> Self {
         // parse `secretsstr`, flip 'visible' every CONF_LINE_SECRET_MODIFIER__VISIBLE
-        let secret = Secret::new(secretstr);
+        let secret = Secret::new(secretstr, conf);
         Self {
             secret,
             lifes,
-            last_guess: ' ',
+            max_lifes: lifes,
+            last_guess: String::from(" "),
+            last_outcome: None,
+            correct_guesses: BTreeSet::new(),
+            missed_guesses: BTreeSet::new(),
+            revealed: 0,
+            hinted: 0,
             state: State::Ongoing,
             last_game,
         }
     }
 
     /// Process a guess and modify the game state.
-    pub fn guess(&mut self, character: char) {
-        if character == '\n' {
+    /// Characters guessed before do not cost a life. A guess is one token of
+    /// the secret, see `SecretConf::tokenize()`.
+    pub fn guess(&mut self, token: &str) {
+        if token.is_empty() || token == "\n" {
             return;
         };
-        self.last_guess = character;
+        self.last_guess = token.to_string();
+
+        let key = self.secret.fold(token);
+        if self.correct_guesses.contains(&key) || self.missed_guesses.contains(&key) {
+            self.last_outcome = Some(GuessOutcome::Repeated);
+            return;
+        }
 
-        let found = self.secret.guess(character);
+        let found = self.secret.guess(token);
 
-        if !found {
+        if found {
+            self.correct_guesses.insert(key);
+            self.last_outcome = Some(GuessOutcome::Correct);
+        } else {
+            self.missed_guesses.insert(key);
+            self.last_outcome = Some(GuessOutcome::Missed);
             self.lifes -= 1;
         }
 
+        self.update_state();
+    }
+
+    /// Refuses to take `token` as a guess, e.g. because it is not part of
+    /// the alphabet. This costs nothing.
+    pub fn reject(&mut self, token: &str) {
+        self.last_guess = token.to_string();
+        self.last_outcome = Some(GuessOutcome::Rejected);
+    }
+
+    /// Can a hint costing `cost` lives be given? At least one life must remain
+    /// afterwards, so hints are never given when only one life is left.
+    pub fn is_hint_available(&self, cost: u8) -> bool {
+        self.state == State::Ongoing && self.lifes > cost.max(1)
+    }
+
+    /// Discloses one randomly chosen hidden character of the secret in exchange
+    /// for `cost` lives.
+    pub fn hint<R: Rng + ?Sized>(&mut self, cost: u8, rng: &mut R) {
+        if !self.is_hint_available(cost) {
+            self.last_outcome = Some(GuessOutcome::HintRefused);
+            return;
+        }
+
+        let hidden = self.secret.hidden_chars();
+        if let Some(character) = self.secret.disclose_random(rng) {
+            self.hinted += hidden - self.secret.hidden_chars();
+            self.correct_guesses.insert(self.secret.fold(&character));
+            self.last_guess = character;
+            self.last_outcome = Some(GuessOutcome::Hint);
+            self.lifes -= cost;
+        }
+
+        self.update_state();
+    }
+
+    /// The time for a guess has run out. This costs a life like a wrong guess.
+    pub fn timeout(&mut self) {
+        if self.state != State::Ongoing {
+            return;
+        }
+        self.last_outcome = Some(GuessOutcome::Timeout);
+        self.lifes -= 1;
+
+        self.update_state();
+    }
+
+    /// Derives `state` from the secret and the lives left.
+    fn update_state(&mut self) {
+        self.revealed = self.secret.chars_to_guess() - self.secret.hidden_chars();
+
         self.state = if self.lifes == 0 {
             // Disclose the secret
             self.secret.disclose_all();
@@ -88,48 +194,151 @@ impl fmt::Display for Game {
 // ***********************
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
 
     /// Play simulation
     #[test]
     fn test_game_simulation() {
-        let mut game = Game::new("_ab _cd", 2, true);
+        let mut game = Game::new("_ab _cd", &SecretConf::default(), 2, true);
         //println!("{:?}",game);
 
         assert_eq!(format!("{}", game.secret), " a b   _ _\n");
         assert_eq!(game.lifes, 2);
-        assert_eq!(game.last_guess, ' ');
+        assert_eq!(game.last_guess, " ");
         assert_eq!(game.state, State::Ongoing);
         assert_eq!(game.last_game, true);
 
         // now we guess right
-        game.guess('c');
+        game.guess("c");
         //println!("{:?}",game);
 
         assert_eq!(format!("{}", game.secret), " a b   c _\n");
         assert_eq!(game.lifes, 2);
-        assert_eq!(game.last_guess, 'c');
+        assert_eq!(game.last_guess, "c");
         assert_eq!(game.state, State::Ongoing);
         assert_eq!(game.last_game, true);
 
         // now we guess wrong
-        game.guess('x');
+        game.guess("x");
         //println!("{:?}",game);
 
         assert_eq!(format!("{}", game.secret), " a b   c _\n");
         assert_eq!(game.lifes, 1);
-        assert_eq!(game.last_guess, 'x');
+        assert_eq!(game.last_guess, "x");
         assert_eq!(game.state, State::Ongoing);
         assert_eq!(game.last_game, true);
 
         // we guess wrong again and we loose
-        game.guess('y');
+        game.guess("y");
         //println!("{:?}",game);
         assert_eq!(format!("{}", game.secret), " a b   c d\n");
         assert_eq!(game.lifes, 0);
-        assert_eq!(game.last_guess, 'y');
+        assert_eq!(game.last_guess, "y");
         assert_eq!(game.state, State::DefeatGameOver);
+        // Disclosing the secret after the defeat does not count.
+        assert_eq!(game.revealed, 1);
         assert_eq!(game.last_game, true);
     }
+
+    /// Repeated guesses do not cost a life.
+    #[test]
+    fn test_game_repeated_guess() {
+        let mut game = Game::new("_ab _cd", &SecretConf::default(), 2, true);
+        assert_eq!(game.last_outcome, None);
+
+        game.guess("x");
+        assert_eq!(game.lifes, 1);
+        assert_eq!(game.last_outcome, Some(GuessOutcome::Missed));
+
+        // The same wrong guess again.
+        game.guess("x");
+        assert_eq!(game.lifes, 1);
+        assert_eq!(game.last_outcome, Some(GuessOutcome::Repeated));
+        assert_eq!(game.state, State::Ongoing);
+
+        game.guess("c");
+        assert_eq!(game.last_outcome, Some(GuessOutcome::Correct));
+
+        // The same right guess again, in upper case.
+        game.guess("C");
+        assert_eq!(game.lifes, 1);
+        assert_eq!(game.last_guess, "C");
+        assert_eq!(game.last_outcome, Some(GuessOutcome::Repeated));
+
+        assert_eq!(
+            game.correct_guesses
+                .iter()
+                .map(String::as_str)
+                .collect::<String>(),
+            "c"
+        );
+        assert_eq!(
+            game.missed_guesses
+                .iter()
+                .map(String::as_str)
+                .collect::<String>(),
+            "x"
+        );
+    }
+
+    /// Hints disclose a character and cost lives.
+    #[test]
+    fn test_game_hint() {
+        let mut game = Game::new("_ab _cd", &SecretConf::default(), 3, true);
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!(game.is_hint_available(1));
+        assert!(!game.is_hint_available(3));
+
+        game.hint(1, &mut rng);
+        assert_eq!(game.lifes, 2);
+        assert_eq!(game.last_outcome, Some(GuessOutcome::Hint));
+        assert!(game.last_guess == "c" || game.last_guess == "d");
+        assert_eq!(game.secret.hidden_chars(), 1);
+        assert_eq!(game.state, State::Ongoing);
+
+        // Guessing the hinted character again costs nothing.
+        let hinted = game.last_guess.clone();
+        game.guess(&hinted);
+        assert_eq!(game.last_outcome, Some(GuessOutcome::Repeated));
+
+        game.guess("x");
+        assert_eq!(game.lifes, 1);
+
+        // Only one life left.
+        assert!(!game.is_hint_available(0));
+        game.hint(1, &mut rng);
+        assert_eq!(game.lifes, 1);
+        assert_eq!(game.last_outcome, Some(GuessOutcome::HintRefused));
+        assert_eq!(game.secret.hidden_chars(), 1);
+    }
+
+    /// Running out of time costs a life.
+    #[test]
+    fn test_game_timeout() {
+        let mut game = Game::new("_ab _cd", &SecretConf::default(), 2, true);
+        game.timeout();
+        assert_eq!(game.lifes, 1);
+        assert_eq!(game.last_outcome, Some(GuessOutcome::Timeout));
+        assert_eq!(game.state, State::Ongoing);
+        game.timeout();
+        assert_eq!(game.lifes, 0);
+        assert_eq!(game.state, State::DefeatGameOver);
+        // No more lives to lose.
+        game.timeout();
+        assert_eq!(game.lifes, 0);
+    }
+
+    /// The last character disclosed by a hint wins the game.
+    #[test]
+    fn test_game_hint_victory() {
+        let mut game = Game::new("_ab _c", &SecretConf::default(), 7, false);
+        game.hint(2, &mut StdRng::seed_from_u64(0));
+        assert_eq!(game.lifes, 5);
+        assert_eq!(game.last_guess, "c");
+        assert_eq!(game.state, State::Victory);
+    }
 }
@@ -4,14 +4,18 @@
 
 use crate::ascii_art::DEFAULT_IMAGES;
 use crate::ascii_art::IMAGE_KNOWN_SIGNATURES;
+use crate::dictionary::has_yaml_key;
+use crate::dictionary::is_yaml_config;
 use crate::dictionary::ConfigParseError;
+use crate::dictionary::LegacyConfig;
 use crate::game::Game;
-use crate::LIVES;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
-use serde_derive::Deserialize;
+use rand::Rng;
+use serde_derive::{Deserialize, Serialize};
 use std::cmp::{Ord, Ordering};
 use std::fmt;
+use unicode_normalization::UnicodeNormalization;
+use unicode_width::UnicodeWidthChar;
 
 /// Default game mode. Can be changed in the configuration file.
 const DEFAULT_REWARDING_SCHEME: RewardingScheme = RewardingScheme::UnhideWhenGuessedChar;
@@ -22,7 +26,7 @@ const DEFAULT_REWARDING_SCHEME: RewardingScheme = RewardingScheme::UnhideWhenGue
 const BIG_IMAGE: usize = 60; // sort algorithm <-> random algorithm
 
 /// A game mode defining how the ASCII-art image will be disclosed progressively.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum RewardingScheme {
     /// Game mode that is used together with the traditional gallows image (the gallows image
     /// is not build in, but can be added in the configuration file. The image is disclosed
@@ -32,12 +36,21 @@ pub enum RewardingScheme {
     UnhideWhenGuessedChar,
 }
 /// One character of the ASCII art image.
-#[derive(Eq, PartialEq, Debug, Copy, Clone)] //omitting Ord
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Serialize, Deserialize)] //omitting Ord
 pub struct ImChar {
-    pub point: (u8, u8),
+    /// Column and line. Columns count the display width of the preceding
+    /// characters, e.g. full-width CJK characters take two columns.
+    pub point: (u16, u16),
     pub code: char,
 }
 
+/// Number of columns the character takes on the display. Characters without
+/// width, e.g. combining marks, are drawn on top of the preceding character.
+/// Control characters count one column.
+fn char_width(c: char) -> usize {
+    c.width().unwrap_or(1)
+}
+
 /// Format an image character.
 impl fmt::Display for ImChar {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
@@ -66,11 +79,11 @@ impl Ord for ImChar {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 /// An ASCII-art image.
 pub struct Image {
     pub ichars: Vec<ImChar>,
-    pub dimension: (u8, u8),
+    pub dimension: (u16, u16),
     pub visible_points: usize,
     pub rewarding_scheme: RewardingScheme,
 }
@@ -81,32 +94,55 @@ impl fmt::Display for Image {
         let x_max = self.dimension.0 as usize;
         let y_max = self.dimension.1 as usize;
 
-        let mut i = vec![' '; ((x_max + 1) * y_max) as usize];
-        for y in 0..y_max {
-            i[((x_max + 1) * y + x_max) as usize] = '\n';
-        }
+        // Every cell holds a character followed by the characters without width
+        // drawn on top of it. `None` marks cells covered by a wide character.
+        let mut i: Vec<Option<String>> = vec![Some(String::from(" ")); x_max * y_max];
 
         for ic in self.ichars.iter().take(self.visible_points) {
             let &ImChar {
                 point: (x, y),
                 code,
             } = ic;
-            i[(x as usize + y as usize * (x_max + 1))] = code;
+            let n = x as usize + y as usize * x_max;
+            match char_width(code) {
+                0 => {
+                    if let Some(cell) = &mut i[n] {
+                        cell.push(code);
+                    }
+                }
+                width => {
+                    if let Some(cell) = &mut i[n] {
+                        let base = cell.chars().next().map_or(0, char::len_utf8);
+                        cell.replace_range(..base, code.encode_utf8(&mut [0; 4]));
+                    }
+                    for covered in i.iter_mut().skip(n + 1).take(width - 1) {
+                        *covered = None;
+                    }
+                }
+            }
         }
 
-        write!(f, "{}", i.into_iter().collect::<String>())
+        for line in i.chunks(x_max.max(1)).take(y_max) {
+            for cell in line.iter().flatten() {
+                write!(f, "{}", cell)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
     }
 }
 
 impl Image {
     /// Returns a random built-in image.
-    pub fn new() -> Result<Self, ConfigParseError> {
-        let mut rng = thread_rng();
-        Self::from_yaml((DEFAULT_IMAGES).choose(&mut rng).unwrap())
+    pub fn new<R: Rng + ?Sized>(rng: &mut R) -> Result<Self, ConfigParseError> {
+        Self::from_yaml((DEFAULT_IMAGES).choose(rng).unwrap(), rng)
     }
 
     /// Constructor reading image data from YAML configuration files.
-    pub fn from_yaml(input: &str) -> Result<Self, ConfigParseError> {
+    /// When the data has neither a `secrets:` nor an `image:` line, it is
+    /// read in the legacy line-based format.
+    /// `rng` determines the order in which the pixels of big images appear.
+    pub fn from_yaml<R: Rng + ?Sized>(input: &str, rng: &mut R) -> Result<Self, ConfigParseError> {
         #[derive(Debug, PartialEq, Deserialize)]
         pub struct RawImage {
             image: Option<String>,
@@ -115,7 +151,14 @@ impl Image {
 
         let input = input.trim_start_matches('\u{feff}');
 
-        let raw: RawImage = serde_yaml::from_str(input)?;
+        let raw = if !is_yaml_config(input) && !has_yaml_key(input, "image") {
+            let LegacyConfig {
+                image, traditional, ..
+            } = LegacyConfig::from(input)?;
+            RawImage { image, traditional }
+        } else {
+            serde_yaml::from_str(input)?
+        };
 
         let (image, rewarding_scheme) = match raw {
             RawImage { image: None, .. } => return Err(ConfigParseError::NoImageData),
@@ -136,12 +179,35 @@ impl Image {
             ),
         };
 
-        Self::from(&image, rewarding_scheme)
+        Self::from(&image, rewarding_scheme, rng)
     }
 
     #[inline]
-    /// This constructor takes a pure ASCII, non-escaped, multiline image string.
-    pub fn from(image: &str, rewarding_scheme: RewardingScheme) -> Result<Self, ConfigParseError> {
+    /// This constructor takes a non-escaped, multiline image string. Columns
+    /// are counted in display width, see `char_width()`.
+    pub fn from<R: Rng + ?Sized>(
+        image: &str,
+        rewarding_scheme: RewardingScheme,
+        rng: &mut R,
+    ) -> Result<Self, ConfigParseError> {
+        // Composed and decomposed forms of a character must look the same.
+        let image: String = image.nfc().collect();
+
+        // The coordinates must fit in `u16`.
+        let width = image
+            .lines()
+            .map(|l| l.chars().map(char_width).sum())
+            .max()
+            .unwrap_or_default();
+        let height = image.lines().count();
+        if width > u16::MAX as usize || height > u16::MAX as usize {
+            return Err(ConfigParseError::ImageTooBig {
+                width,
+                height,
+                max: u16::MAX,
+            });
+        }
+
         let mut ascii: Vec<ImChar> = Vec::new();
         let mut signature: Vec<ImChar> = Vec::new();
 
@@ -166,52 +232,31 @@ impl Image {
             }
             debug_assert_eq!(line.len(), ascii_line.len());
 
-            // Generate `ImChar` pixel from `ascii_line`.
-            let mut ii: Vec<_> = ascii_line
-                .char_indices()
-                // consider only chars != ' '
-                .filter(|&(_, c)| c != ' ')
-                // save in ImChar object
-                .map(|(x, c)| ImChar {
-                    point: ((x) as u8, y as u8),
-                    code: c,
-                })
-                .collect();
-            ascii.append(&mut ii);
-
-            // Check what we have changed and generate
-            // `signature_line`.
-            let mut signature_line = String::new();
+            // Generate `ImChar` pixels from `ascii_line`. What we have changed
+            // there, are the `signature` pixels.
+            let mut x = 0;
+            let mut last_x = 0;
             for (l, a) in line.chars().zip(ascii_line.chars()) {
-                if l == a {
-                    // Nothing changed here.
-                    signature_line.push(' ');
-                } else {
-                    signature_line.push(l);
+                let width = char_width(l);
+                // Characters without width belong to the preceding column.
+                let point = (if width == 0 { last_x } else { x } as u16, y as u16);
+                if l != a {
+                    signature.push(ImChar { point, code: l });
+                } else if a != ' ' {
+                    ascii.push(ImChar { point, code: a });
+                }
+                if width > 0 {
+                    last_x = x;
+                    x += width;
                 }
             }
-            debug_assert_eq!(signature_line.chars().count(), ascii_line.chars().count());
-
-            // Generate `ImChar` pixel from `signature_line`.
-            let mut ii: Vec<_> = signature_line
-                .char_indices()
-                // consider only chars != ' '
-                .filter(|&(_, c)| c != ' ')
-                // save in ImChar object
-                .map(|(x, c)| ImChar {
-                    point: ((x) as u8, y as u8),
-                    code: c,
-                })
-                .collect();
-            signature.append(&mut ii);
         }
 
         // Order or shuffle pixel in `ascii`
         if ascii.len() <= BIG_IMAGE {
             ascii.sort(); // Sort algorithm, see "impl Ord for ImageChar"
         } else {
-            let mut rng = thread_rng();
-            (&mut ascii).shuffle(&mut rng); // points appear randomly.
+            ascii.shuffle(rng); // points appear randomly.
         }
 
         // Append `signatures` at the end of `ascii`.
@@ -223,7 +268,11 @@ impl Image {
             let mut y_max = 0;
 
             for i in &ascii {
-                let &ImChar { point: (x, y), .. } = i;
+                let &ImChar {
+                    point: (x, y),
+                    code,
+                } = i;
+                let x = x + char_width(code).max(1) as u16;
                 if x > x_max {
                     x_max = x
                 };
@@ -232,7 +281,7 @@ impl Image {
                 };
             }
             // We know that there is at least one char.
-            (x_max + 1, y_max + 1)
+            (x_max, y_max + 1)
         } else {
             (0, 0)
         };
@@ -261,7 +310,7 @@ impl Image {
                 }
             }
             RewardingScheme::UnhideWhenLostLife => {
-                self.hide((game.lifes as usize, LIVES as usize));
+                self.hide((game.lifes as usize, game.max_lifes as usize));
             }
         };
     }
@@ -270,7 +319,7 @@ impl Image {
     fn hide(&mut self, fraction: (usize, usize)) {
         let l = self.ichars.len();
 
-        let as_points = |(n, d)| (5 * l * (d - n) as usize / d as usize + l) / 6;
+        let as_points = |(n, d)| (5 * l * (d - n) / d + l) / 6;
 
         // silently ignore division by zero
         if fraction.1 > 0 {
@@ -284,8 +333,9 @@ impl Image {
 #[cfg(test)]
 mod tests {
     use super::DEFAULT_REWARDING_SCHEME;
-    use super::{ImChar, Image};
+    use super::{ImChar, Image, RewardingScheme};
     use crate::dictionary::ConfigParseError;
+    use rand::thread_rng;
 
     #[test]
     fn test_image_from() {
@@ -296,8 +346,9 @@ mod tests {
 "#;
         let expected: &str = "         \n>o)      \n(_>   <o)\n      (_>\n";
         let image = Image::from(
-            &config,
+            config,
             crate::image::RewardingScheme::UnhideWhenGuessedChar,
+            &mut thread_rng(),
         )
         .unwrap();
 
@@ -305,13 +356,81 @@ mod tests {
         assert_eq!(format!("{}", image), expected);
     }
 
+    /// Columns are counted in display width, not in bytes.
+    #[test]
+    fn test_image_from_unicode() {
+        let config = "\u{250c}\u{2500}\u{2510}\n\u{2502}\u{e9}\u{2502}\n\u{6f22}x\n";
+        let image = Image::from(config, DEFAULT_REWARDING_SCHEME, &mut thread_rng()).unwrap();
+        assert_eq!(image.dimension, (3, 3));
+        assert!(image.ichars.contains(&ImChar {
+            point: (2, 1),
+            code: '\u{2502}',
+        }));
+        assert!(image.ichars.contains(&ImChar {
+            point: (2, 2),
+            code: 'x',
+        }));
+        assert_eq!(format!("{}", image), config);
+
+        // A hidden wide character keeps its two columns.
+        let mut image = image;
+        image.ichars.retain(|ic| ic.code != '\u{6f22}');
+        image.visible_points = image.ichars.len();
+        assert!(format!("{}", image).ends_with("\n  x\n"));
+
+        // Decomposed accents are composed, other combining marks stay on
+        // top of their character.
+        let config = "e\u{301}q\u{301}|\n";
+        let image = Image::from(config, DEFAULT_REWARDING_SCHEME, &mut thread_rng()).unwrap();
+        assert_eq!(image.dimension, (3, 1));
+        assert!(image.ichars.contains(&ImChar {
+            point: (1, 0),
+            code: '\u{301}',
+        }));
+        assert_eq!(format!("{}", image), "\u{e9}q\u{301}|\n");
+
+        // Wide images do not wrap around.
+        let config = "x".repeat(300);
+        let image = Image::from(&config, DEFAULT_REWARDING_SCHEME, &mut thread_rng()).unwrap();
+        assert_eq!(image.dimension, (300, 1));
+        assert_eq!(image.ichars.iter().map(|ic| ic.point.0).max(), Some(299));
+
+        let config = "\u{ff58}".repeat(u16::MAX as usize / 2 + 1);
+        let err = Image::from(&config, DEFAULT_REWARDING_SCHEME, &mut thread_rng()).unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigParseError::ImageTooBig {
+                width: 65536,
+                height: 1,
+                max: u16::MAX
+            }
+        ));
+    }
+
     #[test]
     fn test_image_yaml_error() {
-        let config: &str = "this is no image";
-        let image = Image::from_yaml(&config).unwrap_err();
+        let config: &str = "image: [this is no image";
+        let image = Image::from_yaml(config, &mut thread_rng()).unwrap_err();
         //println!("{:?}",image);
 
         assert!(matches!(image, ConfigParseError::NotInYamlFormat(_)));
+
+        // This is a valid configuration in the legacy format, but without image.
+        let config: &str = "this is no image";
+        let image = Image::from_yaml(config, &mut thread_rng()).unwrap_err();
+
+        assert!(matches!(image, ConfigParseError::NoImageData));
+    }
+
+    /// Test image parsing of legacy configuration file data
+    #[test]
+    fn test_image_from_legacy() {
+        let config: &str = "guess me\n:traditional-rewarding\n|>o)\n|(_>   <o)\n|      (_>\n";
+        let expected: &str = ">o)      \n(_>   <o)\n      (_>\n";
+        let image = Image::from_yaml(config, &mut thread_rng()).unwrap();
+
+        assert_eq!(image.rewarding_scheme, RewardingScheme::UnhideWhenLostLife);
+        assert_eq!(format!("{}", image), expected);
     }
 
     /// Test image parsing of configuration file data
@@ -321,7 +440,7 @@ mod tests {
  ab
  c e
  df"#;
-        let image = Image::from_yaml(&config);
+        let image = Image::from_yaml(config, &mut thread_rng());
         //println!("{:?}",image);
         let expected = Ok(Image {
             ichars: [
@@ -366,7 +485,7 @@ mod tests {
  ab
  c
 # Comment"#;
-        let image = Image::from_yaml(&config).unwrap();
+        let image = Image::from_yaml(config, &mut thread_rng()).unwrap();
         //println!("{:?}",image);
         let expected = Image {
             ichars: [
@@ -400,7 +519,7 @@ mod tests {
        (_>
 "#;
         let expected: &str = ">o)      \n(_>   <o)\n      (_>\n";
-        let image = Image::from_yaml(&config).unwrap();
+        let image = Image::from_yaml(config, &mut thread_rng()).unwrap();
 
         assert!(image.visible_points > 0);
         assert_eq!(format!("{}", image), expected);
@@ -411,7 +530,7 @@ mod tests {
         //
         // Test yaml.
         let config: &str = "image: |1\n abdef\n c";
-        let mut image = Image::from_yaml(&config).unwrap();
+        let mut image = Image::from_yaml(config, &mut thread_rng()).unwrap();
         //println!("{:?}",image);
         let expected = Image {
             ichars: [
@@ -460,7 +579,7 @@ mod tests {
     #[test]
     fn disclose_signature_last() {
         let image_str = "image: |1\n jensB\n AlisC";
-        let image = Image::from_yaml(&image_str).unwrap();
+        let image = Image::from_yaml(image_str, &mut thread_rng()).unwrap();
         //println!("{:?}",image);
         let expected = Image {
             ichars: [
@@ -1,15 +1,30 @@
 //! This module provides the backend API for the game logic
 
 mod ascii_art;
+mod check;
 mod dictionary;
 pub mod game;
 mod image;
+mod players;
 mod secret;
-use crate::dictionary::ConfigParseError;
+pub mod statistics;
+mod teams;
+pub use crate::dictionary::ConfigParseError;
+use crate::dictionary::DefeatPolicy;
 use crate::dictionary::Dict;
+use crate::dictionary::SecretEntry;
 use crate::game::Game;
+use crate::game::GuessOutcome;
 use crate::game::State;
 use crate::image::Image;
+use crate::players::Players;
+use crate::secret::SecretConf;
+use crate::statistics::{RoundReport, SessionReport};
+use crate::teams::Teams;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 pub const VERSION: Option<&'static str> = option_env!("CARGO_PKG_VERSION");
 pub const AUTHOR: &str = "(c) Jens Getreu, 2016-2021.";
@@ -24,8 +39,17 @@ pub const TITLE: &str = "ASCII-Hangman for Kids\n";
 /// games should start with the same image until it changes.
 pub const CHANGE_IMAGE_MAX: usize = 5;
 
-/// Number of wrong guess allowed.
+/// Default number of wrong guesses allowed. Can be changed in the configuration file.
 pub const LIVES: u8 = 7;
+
+/// Upper limit for the number of lives in the configuration file.
+pub const LIVES_MAX: u8 = 30;
+
+/// Default number of lives a hint costs. Can be changed in the configuration file.
+pub const HINT_COST: u8 = 1;
+
+/// The user input requesting a hint: one hidden character of the secret gets disclosed.
+pub const HINT_REQUEST: char = '?';
 /// Fallback sample configuration when the desktop application finds no configuration.
 /// This is also place holder and default when the web application starts.
 pub const CONF_TEMPLATE: &str = "# Add own secrets here; one per line.\r
@@ -55,12 +79,39 @@ secrets:\r
 ";
 
 /// State of the application.
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Backend {
     dict: Dict,
     game: Game,
     image: Image,
     change_image: Option<usize>,
+    secret_conf: SecretConf,
+    /// The secret of the current game with its additional information.
+    entry: SecretEntry,
+    /// Lost secrets asked again after all others, see `DefeatPolicy::RequeueLast`.
+    deferred: Vec<SecretEntry>,
+    /// How often every secret was lost.
+    defeats: BTreeMap<String, u32>,
+    /// Lost secrets that are not asked again, see `DefeatPolicy::Drop`.
+    review: Vec<String>,
+    /// The players taking turns, when there are several.
+    players: Players,
+    /// The teams playing alternate secrets, when there is a competition.
+    teams: Teams,
+    /// Statistics about the secrets played so far.
+    report: SessionReport,
+    /// When the current game started, in seconds since the Unix epoch.
+    game_start: u64,
+    /// When the current guess started, in seconds since the Unix epoch.
+    /// Only relevant with a `timer:`.
+    guess_start: u64,
+    /// The remaining time as shown after the last `process_time()`.
+    #[serde(skip)]
+    timer_shown: String,
+    /// Source of all random choices: the secrets, the images and the hints.
+    /// A restored session continues with a fresh random source.
+    #[serde(skip, default = "StdRng::from_entropy")]
+    rng: StdRng,
 }
 
 /// API to interact with all game logic. This is used by the desktop frontend
@@ -71,14 +122,81 @@ pub trait HangmanBackend {
     where
         Self: std::marker::Sized;
 
+    /// Like `new()`, but all random choices derive from `seed`: the same seed and
+    /// the same configuration replay exactly the same games.
+    fn with_seed(config: &str, seed: u64) -> Result<Self, ConfigParseError>
+    where
+        Self: std::marker::Sized;
+
+    /// Like `new()`, but the secrets are taken from the `lessons:` entry named `lesson`.
+    /// With a `seed`, the random choices are reproducible, see `with_seed()`.
+    fn with_lesson(
+        config: &str,
+        lesson: Option<&str>,
+        seed: Option<u64>,
+    ) -> Result<Self, ConfigParseError>
+    where
+        Self: std::marker::Sized;
+
+    /// The names of the `lessons:` in the configuration, to choose from before
+    /// the session starts. Empty when there are none.
+    fn lessons(config: &str) -> Result<Vec<String>, ConfigParseError>
+    where
+        Self: std::marker::Sized;
+
+    /// Merges the configurations of several files into one, that can be passed
+    /// to the constructors. `sources` pairs the name of each file with its content.
+    /// Errors name the file they come from.
+    fn merge_configs(sources: &[(&str, &str)]) -> Result<String, ConfigParseError>
+    where
+        Self: std::marker::Sized;
+
+    /// Reads the files named in the `include:` list of `config`, the content of
+    /// the file `file`, with `read`. Relative names are relative to `file`.
+    /// Returns all configurations, ready for `merge_configs()`.
+    fn resolve_includes<F>(
+        file: &str,
+        config: &str,
+        read: &mut F,
+    ) -> Result<Vec<(String, String)>, ConfigParseError>
+    where
+        Self: std::marker::Sized,
+        F: FnMut(&std::path::Path) -> std::io::Result<String>;
+
+    /// Finds problems in the configuration, e.g. duplicate secrets or secrets
+    /// without characters to guess. Returns one message per problem.
+    fn check(config: &str) -> Vec<String>
+    where
+        Self: std::marker::Sized;
+
+    /// Continue a session saved with `save()`, exactly where it stopped.
+    fn restore(session: &str) -> Result<Self, ConfigParseError>
+    where
+        Self: std::marker::Sized;
+
+    /// Serialize the whole session state, including the remaining secrets and
+    /// how much of the image is disclosed.
+    fn save(&self) -> Result<String, ConfigParseError>;
+
+    /// Replaces the players configured with `players:`. Players take turns:
+    /// a player keeps the turn after a correct guess and passes it on after a miss.
+    fn set_players(&mut self, names: &[String]);
+
     /// The user_input is a key stroke. The meaning depends on the game's state:
     fn process_user_input(&mut self, inp: &str);
 
+    /// Time-aware entry point. Call it regularly, e.g. every second, with the
+    /// current time in seconds since the Unix epoch, see `statistics::now()`.
+    /// When the `timer:` has run out, the guess is lost like a wrong letter.
+    /// Returns true, when the rendering has changed: a life was lost or the
+    /// seconds left, see `render_timer()`, are different.
+    fn process_time(&mut self, now: u64) -> bool;
+
     /// Renders the image. Make sure it is up to date with `self.image.update()`.
     fn render_image(&self) -> String;
 
     /// Forward the private image dimension
-    fn get_image_dimension(&self) -> (u8, u8);
+    fn get_image_dimension(&self) -> (u16, u16);
 
     /// Renders the partly hidden secret.
     fn render_secret(&self) -> String;
@@ -89,33 +207,106 @@ pub trait HangmanBackend {
     /// Informs about some game statistics: last guess
     fn render_game_last_guess(&self) -> String;
 
+    /// Informs about the seconds left for the current guess. Empty without `timer:`.
+    fn render_timer(&self, now: u64) -> String;
+
+    /// Informs about some game statistics: guessed characters found in the secret
+    fn render_game_correct_guesses(&self) -> String;
+
+    /// Informs about some game statistics: guessed characters not found in the secret
+    fn render_game_missed_guesses(&self) -> String;
+
+    /// Renders the hint of the secret when the game is over. Empty when there is none.
+    fn render_hint(&self) -> String;
+
+    /// Renders the translation of the secret when the game is over. Empty when there is none.
+    fn render_translation(&self) -> String;
+
+    /// Renders the name of the player and the team whose turn it is.
+    /// Empty without players and teams.
+    fn render_current_player(&self) -> String;
+
+    /// Renders the number of games every player has won. Empty without players.
+    fn render_player_scores(&self) -> String;
+
+    /// Renders the points of every team, one team per line. Empty without teams.
+    fn render_scoreboard(&self) -> String;
+
     /// Tells the user what to do next.
     fn render_instructions(&self) -> String;
 
+    /// Renders the lost secrets, that were not asked again, when the session is over.
+    /// Empty when there are none.
+    fn render_review(&self) -> String;
+
     /// Forwards the game's state
     fn get_state(&self) -> State;
+
+    /// Is the session over, because there are no more secrets to play?
+    fn is_game_over(&self) -> bool;
+
+    /// Can the player request a hint with `HINT_REQUEST` now?
+    fn is_hint_available(&self) -> bool;
+
+    /// Statistics about every secret played in this session.
+    fn get_report(&self) -> SessionReport;
 }
 
 impl HangmanBackend for Backend {
     fn new(config: &str) -> Result<Self, ConfigParseError> {
-        let mut dict = Dict::from(config)?;
-        // A dictionary guaranties to have least one secret.
-        let secret = dict.get_random_secret().unwrap();
-        let game = Game::new(&secret, LIVES, dict.is_empty());
-        // We assume, that the configuration file comes with a custom image.
-        let mut change_image = None;
-        let mut image = Image::from_yaml(config).or_else(|_| {
-            // We use our built-in images (first game = 0).
-            change_image = Some(0);
-            Image::new()
-        })?;
-        image.update(&game);
-        Ok(Self {
-            dict,
-            game,
-            image,
-            change_image,
-        })
+        Self::from_rng(config, None, StdRng::from_entropy())
+    }
+
+    fn with_seed(config: &str, seed: u64) -> Result<Self, ConfigParseError> {
+        Self::from_rng(config, None, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_lesson(
+        config: &str,
+        lesson: Option<&str>,
+        seed: Option<u64>,
+    ) -> Result<Self, ConfigParseError> {
+        let rng = seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64);
+        Self::from_rng(config, lesson, rng)
+    }
+
+    fn lessons(config: &str) -> Result<Vec<String>, ConfigParseError> {
+        Ok(Dict::from(config).map_err(|e| e.locate(config))?.lessons())
+    }
+
+    fn merge_configs(sources: &[(&str, &str)]) -> Result<String, ConfigParseError> {
+        dictionary::merge_configs(sources)
+    }
+
+    fn resolve_includes<F>(
+        file: &str,
+        config: &str,
+        read: &mut F,
+    ) -> Result<Vec<(String, String)>, ConfigParseError>
+    where
+        F: FnMut(&std::path::Path) -> std::io::Result<String>,
+    {
+        dictionary::resolve_includes(file, config, read)
+    }
+
+    fn check(config: &str) -> Vec<String> {
+        check::check(config)
+    }
+
+    fn restore(session: &str) -> Result<Self, ConfigParseError> {
+        let mut app: Self =
+            serde_yaml::from_str(session).map_err(ConfigParseError::SessionFormat)?;
+        // The time between saving and restoring does not count.
+        app.guess_start = statistics::now();
+        Ok(app)
+    }
+
+    fn save(&self) -> Result<String, ConfigParseError> {
+        serde_yaml::to_string(self).map_err(ConfigParseError::SessionFormat)
+    }
+
+    fn set_players(&mut self, names: &[String]) {
+        self.players.set_names(names);
     }
 
     fn process_user_input(&mut self, inp: &str) {
@@ -123,13 +314,12 @@ impl HangmanBackend for Backend {
             State::Victory => {
                 // Start a new game. As long as we do not get a `State::VictoryGameOver`, we know
                 // that there is at least one secret left.
-                let secret = self.dict.get_random_secret().unwrap();
-                self.game = Game::new(&secret, LIVES, self.dict.is_empty());
+                self.new_game();
                 // We change the image, when we have guessed a certain number of times.
                 if let Some(n) = self.change_image {
                     if n == CHANGE_IMAGE_MAX - 1 {
                         // Switch to the next image.
-                        if let Ok(new_image) = Image::new() {
+                        if let Ok(new_image) = Image::new(&mut self.rng) {
                             self.image = new_image;
                         };
                         self.change_image = Some(0);
@@ -142,28 +332,81 @@ impl HangmanBackend for Backend {
 
             State::VictoryGameOver => {}
 
+            // The lost secret was dropped and there is nothing left.
+            State::DefeatGameOver if self.is_game_over() => {}
+
             State::Defeat | State::DefeatGameOver => {
-                // We will ask this secret again; this way we never end a game with a defeat.
-                self.dict.add((self.game.secret).to_raw_string());
-                // Start a new game. As we just added a secret, we know there is at least one.
-                let secret = self.dict.get_random_secret().unwrap();
-                self.game = Game::new(&secret, LIVES, self.dict.is_empty());
+                // Start a new game. The defeat policy has been applied when the game
+                // was lost: unless the session is over, there is at least one secret left.
+                self.new_game();
                 self.image.update(&self.game);
             }
             State::Ongoing => {
-                self.game.guess(inp.chars().next().unwrap_or(' '));
-                // `guess()` changes the game state:
+                // The first letter or unit is the guess. An empty line has none.
+                let guess = self
+                    .secret_conf
+                    .tokenize(inp.trim_end_matches(['\r', '\n']))
+                    .into_iter()
+                    .next();
+                // Only a hint or a guess restarts the `timer:`.
+                let mut moved = false;
+                if inp.trim() == HINT_REQUEST.to_string() {
+                    self.game.hint(self.dict.hint_cost(), &mut self.rng);
+                    moved = self.game.last_outcome == Some(GuessOutcome::Hint);
+                } else if let Some(guess) = &guess {
+                    if self.secret_conf.is_guessable(guess) {
+                        self.game.guess(guess);
+                        moved = true;
+                    } else {
+                        self.game.reject(guess);
+                    }
+                }
+                // An empty line is not a move.
+                if let (Some(outcome), true) = (&self.game.last_outcome, guess.is_some()) {
+                    self.players
+                        .update(outcome, &self.game.state, &mut self.rng);
+                }
+                if moved {
+                    self.guess_start = statistics::now();
+                }
+                // `guess()` and `hint()` change the game state:
                 self.image.update(&self.game);
+                if self.game.state != State::Ongoing {
+                    self.finish_game();
+                }
             }
         }
     }
 
+    fn process_time(&mut self, now: u64) -> bool {
+        let timer = match (self.dict.timer(), &self.game.state) {
+            (Some(timer), State::Ongoing) => timer,
+            _ => return false,
+        };
+        let mut changed = false;
+        if now.saturating_sub(self.guess_start) >= timer {
+            changed = true;
+            self.game.timeout();
+            self.guess_start = now;
+            self.players
+                .update(&GuessOutcome::Timeout, &self.game.state, &mut self.rng);
+            self.image.update(&self.game);
+            if self.game.state != State::Ongoing {
+                self.finish_game();
+            }
+        }
+        let shown = self.render_timer(now);
+        changed |= shown != self.timer_shown;
+        self.timer_shown = shown;
+        changed
+    }
+
     fn render_image(&self) -> String {
         format!("{}", self.image)
     }
 
     #[allow(dead_code)]
-    fn get_image_dimension(&self) -> (u8, u8) {
+    fn get_image_dimension(&self) -> (u16, u16) {
         self.image.dimension
     }
 
@@ -179,16 +422,641 @@ impl HangmanBackend for Backend {
         format!("Last guess: {}", self.game.last_guess)
     }
 
+    fn render_timer(&self, now: u64) -> String {
+        match (self.dict.timer(), &self.game.state) {
+            (Some(timer), State::Ongoing) => {
+                format!("Time: {} s", (self.guess_start + timer).saturating_sub(now))
+            }
+            _ => String::new(),
+        }
+    }
+
+    fn render_game_correct_guesses(&self) -> String {
+        format!("Correct: {}", join_chars(&self.game.correct_guesses))
+    }
+
+    fn render_game_missed_guesses(&self) -> String {
+        format!("Missed: {}", join_chars(&self.game.missed_guesses))
+    }
+
+    fn render_hint(&self) -> String {
+        match (&self.game.state, &self.entry.hint) {
+            (State::Ongoing, _) | (_, None) => String::new(),
+            (_, Some(hint)) => format!("Hint: {}", hint),
+        }
+    }
+
+    fn render_translation(&self) -> String {
+        match (&self.game.state, &self.entry.translation) {
+            (State::Ongoing, _) | (_, None) => String::new(),
+            (_, Some(translation)) => format!("Translation: {}", translation),
+        }
+    }
+
+    fn render_current_player(&self) -> String {
+        match (self.players.current(), self.teams.current()) {
+            (Some(player), Some(team)) => format!("Turn: {}, team {}", player.name, team.name),
+            (Some(player), None) => format!("Turn: {}", player.name),
+            (None, Some(team)) => format!("Turn: team {}", team.name),
+            (None, None) => String::new(),
+        }
+    }
+
+    fn render_scoreboard(&self) -> String {
+        self.teams
+            .iter()
+            .map(|t| format!("{}: {} points\n", t.name, t.score))
+            .collect()
+    }
+
+    fn render_player_scores(&self) -> String {
+        self.players
+            .iter()
+            .map(|p| format!("{}: {}", p.name, p.score))
+            .collect::<Vec<String>>()
+            .join("  ")
+    }
+
     fn render_instructions(&self) -> String {
         match self.game.state {
             State::Victory => String::from("Congratulations! You won!"),
             State::VictoryGameOver => String::from("Congratulations! You won!"),
             State::Defeat | State::DefeatGameOver => String::from("You lost."),
-            State::Ongoing => String::from("Type a letter, then press [Enter]:"),
+            State::Ongoing => match self.game.last_outcome {
+                Some(GuessOutcome::Repeated) => format!(
+                    "You tried `{}` already. Type another letter, then press [Enter]:",
+                    self.game.last_guess
+                ),
+                Some(GuessOutcome::Timeout) => {
+                    String::from("Time is up! You lost a life. Type a letter, then press [Enter]:")
+                }
+                Some(GuessOutcome::Rejected) => match &self.secret_conf.alphabet {
+                    Some(alphabet) => format!(
+                        "`{}` is not {}. Type a letter, then press [Enter]:",
+                        self.game.last_guess, alphabet
+                    ),
+                    None => String::from("Type a letter, then press [Enter]:"),
+                },
+                Some(GuessOutcome::HintRefused) => String::from(
+                    "Not enough lives left for a hint. Type a letter, then press [Enter]:",
+                ),
+                _ if self.is_hint_available() => format!(
+                    "Type a letter or `{}` for a hint, then press [Enter]:",
+                    HINT_REQUEST
+                ),
+                _ => String::from("Type a letter, then press [Enter]:"),
+            },
+        }
+    }
+
+    fn render_review(&self) -> String {
+        if !self.is_game_over() || self.review.is_empty() {
+            return String::new();
+        }
+        let mut review = String::from("Practise again:\n");
+        for secret in &self.review {
+            review.push_str(&format!("- {}\n", secret));
         }
+        review
     }
 
     fn get_state(&self) -> State {
         self.game.state.clone()
     }
+
+    fn is_game_over(&self) -> bool {
+        match self.game.state {
+            State::VictoryGameOver => true,
+            State::DefeatGameOver => self.dict.is_empty() && self.deferred.is_empty(),
+            _ => false,
+        }
+    }
+
+    fn is_hint_available(&self) -> bool {
+        self.game.is_hint_available(self.dict.hint_cost())
+    }
+
+    fn get_report(&self) -> SessionReport {
+        self.report.clone()
+    }
+}
+
+impl Backend {
+    /// Chooses the next secret and starts a new game with it.
+    /// Secrets deferred by `DefeatPolicy::RequeueLast` come after all others.
+    fn new_game(&mut self) {
+        if self.dict.is_empty() {
+            for entry in self.deferred.drain(..) {
+                self.dict.add(entry);
+            }
+        }
+        self.entry = self.dict.next_secret(&mut self.rng).unwrap();
+        self.game = Game::new(
+            &self.entry.text,
+            &self.secret_conf,
+            self.dict.lives(),
+            self.dict.is_empty() && self.deferred.is_empty(),
+        );
+        self.game_start = statistics::now();
+        self.guess_start = self.game_start;
+        self.teams.next();
+    }
+
+    /// Scores and reports the just finished game. A lost secret is handled
+    /// according to the defeat policy.
+    fn finish_game(&mut self) {
+        self.teams.score(&self.game);
+        self.record_game();
+        if matches!(self.game.state, State::Defeat | State::DefeatGameOver) {
+            self.apply_defeat_policy();
+        }
+    }
+
+    /// Puts the lost secret back into the dictionary or lists it for review.
+    fn apply_defeat_policy(&mut self) {
+        let defeats = self.defeats.entry(self.entry.text.clone()).or_insert(0);
+        *defeats += 1;
+        let defeats = *defeats;
+
+        let requeued = match self.dict.defeat_policy() {
+            DefeatPolicy::Requeue => {
+                self.dict.add(self.entry.clone());
+                true
+            }
+            DefeatPolicy::RequeueLast => {
+                self.deferred.push(self.entry.clone());
+                true
+            }
+            DefeatPolicy::RequeueMax(n) if defeats <= n => {
+                self.dict.add(self.entry.clone());
+                true
+            }
+            DefeatPolicy::RequeueMax(_) | DefeatPolicy::Drop => {
+                self.review.push(self.game.secret.to_plain_string());
+                false
+            }
+        };
+        if let Some(round) = self.report.rounds.last_mut() {
+            round.requeued = requeued;
+        }
+    }
+
+    /// Initialize the application with config data and start the first game
+    /// with the secrets of `lesson`. All random choices are taken from `rng`.
+    pub fn from_rng(
+        config: &str,
+        lesson: Option<&str>,
+        mut rng: StdRng,
+    ) -> Result<Self, ConfigParseError> {
+        let locate = |e: ConfigParseError| e.locate(config);
+        let mut dict = Dict::from(config).map_err(locate)?;
+        if let Some(lesson) = lesson {
+            dict.select_lesson(lesson).map_err(locate)?;
+        }
+        let secret_conf = SecretConf::from_yaml(config).map_err(locate)?;
+        let players = Players::from_yaml(config).map_err(locate)?;
+        let teams = Teams::from_yaml(config).map_err(locate)?;
+        dict.shuffle_once(&mut rng);
+        // A dictionary guaranties to have least one secret.
+        let entry = dict.next_secret(&mut rng).unwrap();
+        let game = Game::new(&entry.text, &secret_conf, dict.lives(), dict.is_empty());
+        // We assume, that the configuration file comes with a custom image.
+        let mut change_image = None;
+        let mut image = match Image::from_yaml(config, &mut rng) {
+            Ok(image) => image,
+            Err(ConfigParseError::NoImageData) => {
+                // We use our built-in images (first game = 0).
+                change_image = Some(0);
+                Image::new(&mut rng)?
+            }
+            Err(e) => return Err(locate(e)),
+        };
+        image.update(&game);
+        Ok(Self {
+            dict,
+            game,
+            image,
+            change_image,
+            secret_conf,
+            entry,
+            deferred: Vec::new(),
+            defeats: BTreeMap::new(),
+            review: Vec::new(),
+            players,
+            teams,
+            report: SessionReport::default(),
+            game_start: statistics::now(),
+            guess_start: statistics::now(),
+            timer_shown: String::new(),
+            rng,
+        })
+    }
+
+    /// Adds the statistics of the just finished game to the report.
+    fn record_game(&mut self) {
+        self.report.rounds.push(RoundReport {
+            secret: self.entry.text.clone(),
+            wrong_guesses: self.game.missed_guesses.len(),
+            missed: self.game.missed_guesses.iter().cloned().collect(),
+            defeat: matches!(self.game.state, State::Defeat | State::DefeatGameOver),
+            requeued: false,
+            seconds: statistics::now().saturating_sub(self.game_start),
+        });
+    }
+}
+
+/// Lists characters separated by spaces.
+fn join_chars<'a>(chars: impl IntoIterator<Item = &'a String>) -> String {
+    chars
+        .into_iter()
+        .map(String::as_str)
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A restored session continues exactly where it was saved.
+    #[test]
+    fn test_save_restore() {
+        let config =
+            "secrets:\n- _ab _cd\n- _ef _gh\n- \"_de_r Hund\"\nlives: 5\nimage: |1\n  ab\n  cd\n";
+        let mut app = Backend::new(config).unwrap();
+        app.process_user_input("x");
+        app.process_user_input("?");
+
+        let session = app.save().unwrap();
+        let mut restored = Backend::restore(&session).unwrap();
+
+        assert_eq!(restored.render_secret(), app.render_secret());
+        assert_eq!(restored.render_image(), app.render_image());
+        assert_eq!(restored.render_game_lifes(), "Lifes: 3");
+        assert_eq!(restored.render_game_missed_guesses(), "Missed: x");
+        assert_eq!(restored.dict, app.dict);
+        assert_eq!(restored.change_image, app.change_image);
+        assert_eq!(restored.get_state(), State::Ongoing);
+
+        restored.process_user_input("y");
+        assert_eq!(restored.render_game_lifes(), "Lifes: 2");
+
+        let err = Backend::restore("secrets:\n- guess me\n").unwrap_err();
+        assert!(matches!(err, ConfigParseError::SessionFormat(_)));
+    }
+
+    /// The same seed replays the same secrets, images and hints.
+    #[test]
+    fn test_seed() {
+        let config = "secrets:\n- ab\n- cd\n- ef\n- gh\n- ij\n- kl\n";
+        let play = |seed| {
+            let mut app = Backend::with_seed(config, seed).unwrap();
+            let mut played = Vec::new();
+            while app.get_state() != State::VictoryGameOver {
+                played.push(app.render_image());
+                app.process_user_input("?");
+                played.push(app.render_secret());
+                for c in "abcdefghijkl".chars() {
+                    app.process_user_input(&c.to_string());
+                }
+                played.push(app.render_secret());
+                app.process_user_input("\n");
+            }
+            played
+        };
+
+        assert_eq!(play(42), play(42));
+        assert_ne!(play(42), play(43));
+    }
+
+    /// Players take turns following the classroom rules.
+    #[test]
+    fn test_players() {
+        let config = "secrets:\n- ab\nplayers: [Anna, Ben]\n";
+        let mut app = Backend::new(config).unwrap();
+        assert_eq!(app.render_current_player(), "Turn: Anna");
+        app.process_user_input("a\n");
+        assert_eq!(app.render_current_player(), "Turn: Anna");
+        app.process_user_input("x\n");
+        assert_eq!(app.render_current_player(), "Turn: Ben");
+        // An empty line does not pass the turn.
+        app.process_user_input("\n");
+        assert_eq!(app.render_current_player(), "Turn: Ben");
+        app.process_user_input("b\n");
+        assert_eq!(app.get_state(), State::VictoryGameOver);
+        assert_eq!(app.render_player_scores(), "Anna: 0  Ben: 1");
+
+        app.set_players(&[]);
+        assert_eq!(app.render_current_player(), "");
+        assert_eq!(app.render_player_scores(), "");
+    }
+
+    /// Teams play alternate secrets and score disclosed characters and lives left.
+    #[test]
+    fn test_teams() {
+        let config = "secrets:\n- ab\n- ab\nteams: [Red, Blue]\nlives: 3\n";
+        let mut app = Backend::new(config).unwrap();
+        assert_eq!(app.render_current_player(), "Turn: team Red");
+        app.process_user_input("a");
+        app.process_user_input("x");
+        app.process_user_input("b");
+        assert_eq!(app.get_state(), State::Victory);
+        assert_eq!(app.render_scoreboard(), "Red: 4 points\nBlue: 0 points\n");
+
+        app.process_user_input("\n");
+        assert_eq!(app.render_current_player(), "Turn: team Blue");
+        app.process_user_input("x");
+        app.process_user_input("a");
+        app.process_user_input("y");
+        app.process_user_input("z");
+        assert_eq!(app.get_state(), State::DefeatGameOver);
+        assert_eq!(app.render_scoreboard(), "Red: 4 points\nBlue: 1 points\n");
+
+        // The lost secret is played again by the next team.
+        app.process_user_input("\n");
+        assert_eq!(app.render_current_player(), "Turn: team Red");
+    }
+
+    /// Running out of time costs a life.
+    #[test]
+    fn test_timer() {
+        let config = "secrets:\n- ab\ntimer: 10\nlives: 2\n";
+        let mut app = Backend::new(config).unwrap();
+        let start = app.guess_start;
+        assert!(app.process_time(start + 3));
+        assert_eq!(app.render_timer(start + 3), "Time: 7 s");
+        // The seconds shown did not change.
+        assert!(!app.process_time(start + 3));
+        assert_eq!(app.render_game_lifes(), "Lifes: 2");
+
+        assert!(app.process_time(start + 10));
+        assert_eq!(app.render_game_lifes(), "Lifes: 1");
+        assert!(app.render_instructions().starts_with("Time is up!"));
+        assert_eq!(app.render_timer(start + 10), "Time: 10 s");
+
+        assert!(app.process_time(start + 20));
+        assert_eq!(app.get_state(), State::DefeatGameOver);
+        assert!(!app.process_time(start + 30));
+        assert_eq!(app.render_timer(start + 30), "");
+        assert_eq!(app.get_report().rounds.len(), 1);
+
+        // Neither an empty line nor a refused character restarts the time.
+        let mut app = Backend::new("secrets:\n- ab\ntimer: 10\nalphabet: latin\n").unwrap();
+        app.guess_start = 0;
+        app.process_user_input("\n");
+        app.process_user_input("7\n");
+        assert_eq!(app.guess_start, 0);
+        app.process_user_input("x\n");
+        assert_ne!(app.guess_start, 0);
+
+        // Without timer nothing happens.
+        let mut app = Backend::new("secrets:\n- ab\n").unwrap();
+        assert!(!app.process_time(app.guess_start + 1000));
+        assert_eq!(app.render_game_lifes(), "Lifes: 7");
+        assert_eq!(app.render_timer(app.guess_start), "");
+    }
+
+    /// The secrets are taken from the chosen lesson.
+    #[test]
+    fn test_lessons() {
+        let config = "lessons:\n  colours: [red, blue]\n  animals: [dog]\n";
+        assert_eq!(
+            Backend::lessons(config).unwrap(),
+            vec!["colours".to_string(), "animals".to_string()]
+        );
+        assert!(Backend::lessons("secrets:\n- dog\n").unwrap().is_empty());
+
+        let mut app = Backend::with_lesson(config, Some("animals"), Some(1)).unwrap();
+        assert_eq!(win(&mut app), "dog");
+        assert_eq!(app.get_state(), State::VictoryGameOver);
+
+        // Without a choice, the first lesson is played.
+        let app = Backend::new(config).unwrap();
+        assert!(["red", "blue"].contains(&app.entry.text.as_str()));
+
+        let err = Backend::with_lesson(config, Some("numbers"), None).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("There is no lesson named `numbers` in `lessons:`."));
+    }
+
+    /// Plays the current secret and loses it. Expects `lives: 1`.
+    fn lose(app: &mut Backend) -> String {
+        let secret = app.entry.text.clone();
+        app.process_user_input("0");
+        assert!(matches!(
+            app.get_state(),
+            State::Defeat | State::DefeatGameOver
+        ));
+        secret
+    }
+
+    /// Plays the current secret and wins it.
+    fn win(app: &mut Backend) -> String {
+        let secret = app.entry.text.clone();
+        for c in secret.chars() {
+            app.process_user_input(&c.to_string());
+        }
+        assert!(matches!(
+            app.get_state(),
+            State::Victory | State::VictoryGameOver
+        ));
+        secret
+    }
+
+    /// A lost secret is asked again, like any other remaining secret.
+    #[test]
+    fn test_defeat_requeue() {
+        let config = "secrets:\n- ab\nlives: 1\ndefeat: requeue\n";
+        let mut app = Backend::new(config).unwrap();
+        for _ in 0..3 {
+            lose(&mut app);
+            assert!(!app.is_game_over());
+            app.process_user_input("\n");
+        }
+        win(&mut app);
+        assert!(app.is_game_over());
+        assert_eq!(app.render_review(), "");
+    }
+
+    /// Characters outside the `alphabet:` and empty lines cost nothing.
+    #[test]
+    fn test_alphabet() {
+        let config = "secrets:\n- guess me\nalphabet: latin\n";
+        let mut app = Backend::new(config).unwrap();
+        assert_eq!(app.render_secret(), " _ _ _ _ _   _ _\n");
+
+        app.process_user_input("7\n");
+        assert_eq!(app.game.lifes, LIVES);
+        assert_eq!(
+            app.render_instructions(),
+            "`7` is not a Latin letter. Type a letter, then press [Enter]:"
+        );
+        app.process_user_input("");
+        app.process_user_input("\n");
+        assert_eq!(app.game.lifes, LIVES);
+        app.process_user_input("x\n");
+        assert_eq!(app.game.lifes, LIVES - 1);
+    }
+
+    /// A unit is typed as one guess.
+    #[test]
+    fn test_units() {
+        let config = "secrets:\n- llama\nunits: [ll]\n";
+        let mut app = Backend::new(config).unwrap();
+        assert_eq!(app.render_secret(), " _ _ _ _\n");
+
+        app.process_user_input("l\n");
+        assert_eq!(app.game.lifes, LIVES - 1);
+        app.process_user_input("ll\n");
+        assert_eq!(app.game.lifes, LIVES - 1);
+        assert_eq!(app.render_secret(), " ll _ _ _\n");
+        assert_eq!(app.render_game_correct_guesses(), "Correct: ll");
+    }
+
+    /// A wide custom image is kept, one too big for the coordinates is refused.
+    #[test]
+    fn test_custom_image_size() {
+        let config = format!("secrets:\n- guess me\nimage: |1\n {}\n", "x".repeat(300));
+        let app = Backend::new(&config).unwrap();
+        assert_eq!(app.get_image_dimension(), (300, 1));
+        assert_eq!(app.change_image, None);
+
+        let config = format!(
+            "secrets:\n- guess me\nimage: |1\n {}\n",
+            "\u{ff58}".repeat(u16::MAX as usize / 2 + 1)
+        );
+        let err = Backend::new(&config).unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigParseError::Located { line: 3, ref error, .. }
+                if matches!(**error, ConfigParseError::ImageTooBig { .. })
+        ));
+    }
+
+    /// A `?` in the secret is shown, because typing it asks for a hint.
+    #[test]
+    fn test_hint_request_in_secret() {
+        let config = "secrets:\n- What?\nlives: 1\n";
+        let mut app = Backend::new(config).unwrap();
+        assert_eq!(app.render_secret(), " _ _ _ _ ?\n");
+
+        app.process_user_input("?\n");
+        assert_eq!(app.game.lifes, 1);
+        for c in "what".chars() {
+            app.process_user_input(&c.to_string());
+        }
+        assert_eq!(app.get_state(), State::VictoryGameOver);
+    }
+
+    /// An escaped `_` is still literal, when the lost secret is asked again.
+    #[test]
+    fn test_defeat_requeue_escaped() {
+        let config = "secrets:\n- snake\\_case\nlives: 1\n";
+        let mut app = Backend::new(config).unwrap();
+        assert_eq!(lose(&mut app), "snake\\_case");
+        app.process_user_input("\n");
+        assert_eq!(app.entry.text, "snake\\_case");
+        assert_eq!(app.render_secret(), " _ _ _ _ _ _ _ _ _ _\n");
+        for c in "snake_case".chars() {
+            app.process_user_input(&c.to_string());
+        }
+        assert_eq!(app.get_state(), State::VictoryGameOver);
+    }
+
+    /// A lost secret is asked again after all other secrets.
+    #[test]
+    fn test_defeat_requeue_last() {
+        let config = "secrets:\n- ab\n- cd\n- ef\nlives: 1\ndefeat: requeue-last\n";
+        let mut app = Backend::with_seed(config, 1).unwrap();
+        let lost = lose(&mut app);
+        app.process_user_input("\n");
+        let mut played = vec![win(&mut app)];
+        app.process_user_input("\n");
+        played.push(win(&mut app));
+        assert_eq!(app.get_state(), State::Victory);
+        app.process_user_input("\n");
+        assert_eq!(win(&mut app), lost);
+        assert!(!played.contains(&lost));
+        assert!(app.is_game_over());
+    }
+
+    /// A lost secret is asked again at most N times.
+    #[test]
+    fn test_defeat_requeue_max() {
+        let config = "secrets:\n- _a_b\nlives: 1\ndefeat: 2\n";
+        let mut app = Backend::new(config).unwrap();
+        lose(&mut app);
+        app.process_user_input("\n");
+        lose(&mut app);
+        app.process_user_input("\n");
+        lose(&mut app);
+        assert!(app.is_game_over());
+        assert_eq!(app.render_review(), "Practise again:\n- ab\n");
+        // Nothing happens anymore.
+        app.process_user_input("\n");
+        assert!(app.is_game_over());
+
+        let report = app.get_report();
+        let requeued: Vec<bool> = report.rounds.iter().map(|r| r.requeued).collect();
+        assert_eq!(requeued, vec![true, true, false]);
+    }
+
+    /// A lost secret is not asked again, but listed for review.
+    #[test]
+    fn test_defeat_drop() {
+        let config = "secrets:\n- ab\n- cd\nlives: 1\ndefeat: drop\n";
+        let mut app = Backend::new(config).unwrap();
+        let lost = lose(&mut app);
+        assert_eq!(app.get_state(), State::Defeat);
+        assert_eq!(app.render_review(), "");
+        app.process_user_input("\n");
+        let won = win(&mut app);
+        assert_ne!(won, lost);
+        assert_eq!(app.get_state(), State::VictoryGameOver);
+        assert!(app.is_game_over());
+        assert_eq!(
+            app.render_review(),
+            format!("Practise again:\n- {}\n", lost)
+        );
+
+        // Losing the last secret ends the session.
+        let mut app = Backend::new(config).unwrap();
+        lose(&mut app);
+        app.process_user_input("\n");
+        lose(&mut app);
+        assert_eq!(app.get_state(), State::DefeatGameOver);
+        assert!(app.is_game_over());
+        assert_eq!(app.render_review().lines().count(), 3);
+    }
+
+    /// Every finished game is reported.
+    #[test]
+    fn test_report() {
+        let config = "secrets:\n- _a_b\nlives: 2\n";
+        let mut app = Backend::new(config).unwrap();
+        app.process_user_input("x");
+        app.process_user_input("x");
+        app.process_user_input("y");
+        assert_eq!(app.get_state(), State::DefeatGameOver);
+
+        let report = app.get_report();
+        assert_eq!(report.rounds.len(), 1);
+        assert_eq!(report.rounds[0].secret, "_a_b");
+        assert_eq!(report.rounds[0].wrong_guesses, 2);
+        assert_eq!(report.rounds[0].missed, vec!["x", "y"]);
+        assert!(report.rounds[0].defeat);
+        assert!(report.rounds[0].requeued);
+
+        // Continue: the lost secret is asked again.
+        app.process_user_input("\n");
+        app.process_user_input("b");
+        assert_eq!(app.get_state(), State::VictoryGameOver);
+
+        let report = app.get_report();
+        assert_eq!(report.rounds.len(), 2);
+        assert!(report.rounds[0].requeued);
+        assert!(!report.rounds[1].defeat);
+        assert_eq!(report.rounds[1].wrong_guesses, 0);
+    }
 }
@@ -0,0 +1,185 @@
+//! Enforces the classroom rules when several players take turns:
+//! a player keeps the turn after a correct guess and passes it on after a
+//! miss. The winner of a game is the player who discloses the last letter.
+use crate::dictionary::has_yaml_key;
+use crate::dictionary::is_yaml_config;
+use crate::dictionary::ConfigParseError;
+use crate::game::{GuessOutcome, State};
+use rand::Rng;
+use serde_derive::{Deserialize, Serialize};
+
+/// A participant and the number of secrets they have disclosed completely.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Player {
+    pub name: String,
+    pub score: u32,
+}
+
+/// The player roster and whose turn it is. Without players, there is only
+/// one anonymous player and no turns are managed.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct Players {
+    players: Vec<Player>,
+    /// Index of the player whose turn it is.
+    current: usize,
+    /// When true, the turn passes to a randomly chosen player instead of the next one.
+    random_turns: bool,
+}
+
+impl Players {
+    /// Constructor. Empty names are ignored.
+    pub fn new(names: &[String], random_turns: bool) -> Self {
+        Self {
+            players: names
+                .iter()
+                .map(|n| n.trim())
+                .filter(|n| !n.is_empty())
+                .map(|n| Player {
+                    name: n.to_string(),
+                    score: 0,
+                })
+                .collect(),
+            current: 0,
+            random_turns,
+        }
+    }
+
+    /// Constructor reading the `players:` and `random-turns:` variables from
+    /// YAML configuration files. Configuration files in the legacy format
+    /// have no players.
+    pub fn from_yaml(input: &str) -> Result<Self, ConfigParseError> {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct RawPlayers {
+            players: Option<Vec<String>>,
+            #[serde(rename = "random-turns")]
+            random_turns: Option<bool>,
+        }
+
+        let input = input.trim_start_matches('\u{feff}');
+        if !is_yaml_config(input)
+            && !has_yaml_key(input, "players")
+            && !has_yaml_key(input, "random-turns")
+        {
+            return Ok(Self::default());
+        }
+
+        let raw: RawPlayers = serde_yaml::from_str(input)?;
+
+        Ok(Self::new(
+            &raw.players.unwrap_or_default(),
+            raw.random_turns.unwrap_or_default(),
+        ))
+    }
+
+    /// Replaces the roster, keeping the other settings. Empty names are ignored.
+    pub fn set_names(&mut self, names: &[String]) {
+        *self = Self::new(names, self.random_turns);
+    }
+
+    /// The player whose turn it is.
+    pub fn current(&self) -> Option<&Player> {
+        self.players.get(self.current)
+    }
+
+    /// All players in the order of the roster.
+    pub fn iter(&self) -> impl Iterator<Item = &Player> {
+        self.players.iter()
+    }
+
+    /// Applies the rules after the current player made a move with `outcome`
+    /// that brought the game in `state`: the winner scores, a correct, repeated or
+    /// rejected guess or a hint keeps the turn, a missed guess or a timeout passes it on.
+    pub fn update<R: Rng + ?Sized>(&mut self, outcome: &GuessOutcome, state: &State, rng: &mut R) {
+        if self.players.is_empty() {
+            return;
+        }
+
+        if matches!(state, State::Victory | State::VictoryGameOver) {
+            self.players[self.current].score += 1;
+        }
+
+        match outcome {
+            GuessOutcome::Correct
+            | GuessOutcome::Hint
+            | GuessOutcome::HintRefused
+            | GuessOutcome::Repeated
+            | GuessOutcome::Rejected => {}
+            GuessOutcome::Missed | GuessOutcome::Timeout => self.pass_turn(rng),
+        }
+    }
+
+    /// Gives the turn to the next player, or to a random other player.
+    fn pass_turn<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let n = self.players.len();
+        self.current = if self.random_turns && n > 1 {
+            // Anyone but the current player.
+            (self.current + rng.gen_range(1..n)) % n
+        } else {
+            (self.current + 1) % n
+        };
+    }
+}
+
+// ***********************
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_players_turns() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let config = "secrets:\n- guess me\nplayers:\n- Anna\n- Ben\n- Chris\n";
+        let mut players = Players::from_yaml(config).unwrap();
+        assert_eq!(players.current().unwrap().name, "Anna");
+
+        // A correct guess keeps the turn.
+        players.update(&GuessOutcome::Correct, &State::Ongoing, &mut rng);
+        assert_eq!(players.current().unwrap().name, "Anna");
+
+        // A miss passes the turn, a repeated guess costs nothing and keeps it.
+        players.update(&GuessOutcome::Missed, &State::Ongoing, &mut rng);
+        assert_eq!(players.current().unwrap().name, "Ben");
+        players.update(&GuessOutcome::Repeated, &State::Ongoing, &mut rng);
+        assert_eq!(players.current().unwrap().name, "Ben");
+        players.update(&GuessOutcome::Timeout, &State::Ongoing, &mut rng);
+        assert_eq!(players.current().unwrap().name, "Chris");
+
+        // The player who discloses the last letter wins.
+        players.update(&GuessOutcome::Correct, &State::Victory, &mut rng);
+        assert_eq!(players.current().unwrap().name, "Chris");
+        let scores: Vec<u32> = players.iter().map(|p| p.score).collect();
+        assert_eq!(scores, vec![0, 0, 1]);
+
+        players.update(&GuessOutcome::Missed, &State::Ongoing, &mut rng);
+        assert_eq!(players.current().unwrap().name, "Anna");
+    }
+
+    #[test]
+    fn test_players_random_turns() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let config = "secrets:\n- guess me\nplayers: [Anna, Ben, Chris]\nrandom-turns: true\n";
+        let mut players = Players::from_yaml(config).unwrap();
+        for _ in 0..20 {
+            let before = players.current().unwrap().name.clone();
+            players.update(&GuessOutcome::Missed, &State::Ongoing, &mut rng);
+            assert_ne!(players.current().unwrap().name, before);
+        }
+    }
+
+    #[test]
+    fn test_players_none() {
+        let mut players = Players::from_yaml("secrets:\n- guess me\n").unwrap();
+        players.update(
+            &GuessOutcome::Missed,
+            &State::Ongoing,
+            &mut StdRng::seed_from_u64(0),
+        );
+        assert_eq!(players.current(), None);
+
+        let players = Players::new(&[" ".to_string()], false);
+        assert_eq!(players.current(), None);
+    }
+}
@@ -1,13 +1,330 @@
+use crate::dictionary::has_yaml_key;
+use crate::dictionary::is_yaml_config;
+use crate::dictionary::ConfigParseError;
+use crate::dictionary::CONF_LINE_SECRET_MODIFIER_ESCAPE;
 use crate::dictionary::CONF_LINE_SECRET_MODIFIER_LINEBREAK1;
 use crate::dictionary::CONF_LINE_SECRET_MODIFIER_LINEBREAK2;
 use crate::dictionary::CONF_LINE_SECRET_MODIFIER_VISIBLE;
+use crate::HINT_REQUEST;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde_derive::{Deserialize, Serialize};
 use std::fmt;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
 
 /// Defines the line-break position when displaying the secret string.
-const LINE_WIDTH: usize = 20;
+pub const LINE_WIDTH: usize = 20;
+
+/// Language specific rules for case-insensitive matching of guesses.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub enum CaseFolding {
+    /// Unicode default case folding.
+    #[default]
+    Default,
+    /// Turkish and Azerbaijani: `I` folds to dotless `ı` and `İ` to `i`.
+    Turkic,
+}
+
+impl CaseFolding {
+    /// Chooses the rules for a language tag, e.g. `tr` or `de-CH`.
+    pub fn from_locale(locale: &str) -> Self {
+        let language = locale
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        match language.as_str() {
+            "tr" | "az" => CaseFolding::Turkic,
+            _ => CaseFolding::Default,
+        }
+    }
+
+    /// Maps a character to the form used for case-insensitive comparison.
+    pub fn fold(self, c: char) -> char {
+        match (self, c) {
+            (CaseFolding::Turkic, 'I') => '\u{131}',
+            (_, '\u{130}') => 'i',
+            // These have no upper case form of their own.
+            (_, '\u{3c2}') => '\u{3c3}',
+            (_, '\u{17f}') => 's',
+            (_, c) => {
+                let mut lower = c.to_lowercase();
+                match (lower.next(), lower.next()) {
+                    (Some(l), None) => l,
+                    _ => c,
+                }
+            }
+        }
+    }
+}
+
+/// A class of characters, that can be shown from the start instead of being guessed.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CharClass {
+    Whitespace,
+    /// Punctuation and other symbols: everything but letters, digits and whitespace.
+    Punctuation,
+    Digits,
+}
+
+impl CharClass {
+    /// Is `c` in this class?
+    pub fn contains(self, c: char) -> bool {
+        match self {
+            CharClass::Whitespace => c.is_whitespace(),
+            CharClass::Punctuation => !c.is_alphanumeric() && !c.is_whitespace(),
+            CharClass::Digits => c.is_numeric(),
+        }
+    }
+}
+
+/// The characters that can be guessed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Alphabet {
+    Latin,
+    Greek,
+    Cyrillic,
+    /// The characters of the string.
+    Custom(String),
+}
+
+impl Alphabet {
+    /// Reads the preset names `latin`, `greek` and `cyrillic` in any case.
+    pub fn from_name(name: &str) -> Result<Self, ConfigParseError> {
+        match name.to_lowercase().as_str() {
+            "latin" => Ok(Alphabet::Latin),
+            "greek" => Ok(Alphabet::Greek),
+            "cyrillic" => Ok(Alphabet::Cyrillic),
+            _ => Err(ConfigParseError::UnknownAlphabet {
+                name: name.to_string(),
+            }),
+        }
+    }
+
+    /// Is `token` a letter of this alphabet? Preset alphabets look at the
+    /// base character of the token. The letters of a `Custom` alphabet are
+    /// split into units and compared in the form they are guessed, see
+    /// `SecretConf::fold()`.
+    fn contains(&self, token: &str, conf: &SecretConf) -> bool {
+        let c = token.chars().next().unwrap_or_default();
+        match self {
+            Alphabet::Latin => {
+                c.is_alphabetic()
+                    && matches!(c, 'A'..='Z' | 'a'..='z' | '\u{c0}'..='\u{24f}' | '\u{1e00}'..='\u{1eff}')
+            }
+            Alphabet::Greek => {
+                c.is_alphabetic() && matches!(c, '\u{370}'..='\u{3ff}' | '\u{1f00}'..='\u{1fff}')
+            }
+            Alphabet::Cyrillic => c.is_alphabetic() && matches!(c, '\u{400}'..='\u{52f}'),
+            Alphabet::Custom(letters) => {
+                let token = conf.fold(token);
+                conf.tokenize(letters).iter().any(|l| conf.fold(l) == token)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Alphabet {
+    /// Names a letter of the alphabet, e.g. "a Latin letter".
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Alphabet::Latin => write!(f, "a Latin letter"),
+            Alphabet::Greek => write!(f, "a Greek letter"),
+            Alphabet::Cyrillic => write!(f, "a Cyrillic letter"),
+            Alphabet::Custom(letters) => write!(f, "one of `{}`", letters),
+        }
+    }
+}
+
+/// Settings determining how secrets are parsed and guessed.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct SecretConf {
+    pub case_folding: CaseFolding,
+    /// When true, a guess matches all accented forms of a letter,
+    /// e.g. `e` matches `é`, `è`, `ê` and `ë`.
+    pub ignore_accents: bool,
+    /// Characters of these classes are shown from the start.
+    #[serde(default)]
+    pub visible: Vec<CharClass>,
+    /// These characters are shown from the start.
+    #[serde(default)]
+    pub visible_chars: String,
+    /// Only these characters can be guessed, the others are shown from the
+    /// start. Without, all characters can be guessed.
+    #[serde(default)]
+    pub alphabet: Option<Alphabet>,
+    /// Letters written with more than one character, e.g. `ij`. They are
+    /// guessed as one.
+    #[serde(default)]
+    pub units: Vec<String>,
+}
+
+impl SecretConf {
+    /// Constructor reading the settings from YAML configuration files.
+    /// Configuration files in the legacy format get the defaults.
+    /// Files with settings only, e.g. included ones, need no `secrets:`.
+    pub fn from_yaml(input: &str) -> Result<Self, ConfigParseError> {
+        /// A preset name or `{custom: "abc"}`.
+        #[derive(Debug, PartialEq, Deserialize)]
+        #[serde(untagged)]
+        enum RawAlphabet {
+            Name(String),
+            Custom { custom: String },
+        }
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct RawSecretConf {
+            locale: Option<String>,
+            #[serde(rename = "ignore-accents")]
+            ignore_accents: Option<bool>,
+            visible: Option<Vec<CharClass>>,
+            #[serde(rename = "visible-chars")]
+            visible_chars: Option<String>,
+            alphabet: Option<RawAlphabet>,
+            units: Option<Vec<String>>,
+        }
+
+        let input = input.trim_start_matches('\u{feff}');
+        let keys = [
+            "locale",
+            "ignore-accents",
+            "visible",
+            "visible-chars",
+            "alphabet",
+            "units",
+        ];
+        if !is_yaml_config(input) && !keys.iter().any(|k| has_yaml_key(input, k)) {
+            return Ok(Self::default());
+        }
+
+        let raw: RawSecretConf = serde_yaml::from_str(input)?;
+        let alphabet = match raw.alphabet {
+            None => None,
+            Some(RawAlphabet::Name(name)) => Some(Alphabet::from_name(&name)?),
+            Some(RawAlphabet::Custom { custom }) => Some(Alphabet::Custom(custom)),
+        };
+
+        Ok(Self {
+            case_folding: raw
+                .locale
+                .as_deref()
+                .map(CaseFolding::from_locale)
+                .unwrap_or_default(),
+            ignore_accents: raw.ignore_accents.unwrap_or_default(),
+            visible: raw.visible.unwrap_or_default(),
+            visible_chars: raw.visible_chars.unwrap_or_default(),
+            alphabet,
+            units: raw.units.unwrap_or_default(),
+        })
+    }
+
+    /// Is the token shown from the start, instead of being guessed?
+    /// Character classes look at the base character of the token.
+    pub fn is_visible(&self, token: &str) -> bool {
+        let c = token.chars().next().unwrap_or_default();
+        self.visible.iter().any(|class| class.contains(c))
+            || self
+                .tokenize(&self.visible_chars)
+                .iter()
+                .any(|t| t == token)
+            || !self.is_guessable(token)
+    }
+
+    /// Is the token part of the `alphabet`? The `HINT_REQUEST` never is,
+    /// because typing it asks for a hint.
+    pub fn is_guessable(&self, token: &str) -> bool {
+        token != HINT_REQUEST.to_string()
+            && self
+                .alphabet
+                .as_ref()
+                .is_none_or(|a| a.contains(token, self))
+    }
+
+    /// Splits a string into the tokens guessed as one: the `units` and
+    /// otherwise single grapheme clusters, so that combining marks and emoji
+    /// are never split. The longest matching unit wins.
+    pub fn tokenize(&self, s: &str) -> Vec<String> {
+        // Composed and decomposed forms of a character must guess the same.
+        let s: String = s.nfc().collect();
+        let graphemes: Vec<&str> = s.graphemes(true).collect();
+        let units: Vec<Vec<String>> = self
+            .units
+            .iter()
+            .map(|u| {
+                let u: String = u.nfc().collect();
+                u.graphemes(true).map(|g| self.fold(g)).collect()
+            })
+            .collect();
+
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < graphemes.len() {
+            let n = units
+                .iter()
+                .filter(|u| {
+                    !u.is_empty()
+                        && graphemes[i..]
+                            .iter()
+                            .take(u.len())
+                            .map(|g| self.fold(g))
+                            .eq(u.iter().cloned())
+                })
+                .map(|u| u.len())
+                .max()
+                .unwrap_or(1);
+            tokens.push(graphemes[i..i + n].concat());
+            i += n;
+        }
+        tokens
+    }
+
+    /// Maps a token to the form used for comparison.
+    /// Expects tokens in Unicode normalization form C.
+    pub fn fold(&self, token: &str) -> String {
+        let folded: String = token.chars().map(|c| self.case_folding.fold(c)).collect();
+        if self.ignore_accents {
+            // Keep the base characters only.
+            let base: String = folded.nfd().filter(|&d| !is_combining_mark(d)).collect();
+            if base.is_empty() {
+                folded
+            } else {
+                base
+            }
+        } else {
+            folded
+        }
+    }
+}
+
+/// Is the token the single character `c`?
+fn is_char(token: &str, c: char) -> bool {
+    token.chars().eq([c])
+}
+
+/// Can this token be escaped with `CONF_LINE_SECRET_MODIFIER_ESCAPE`?
+fn is_escapable(token: &str) -> bool {
+    is_char(token, CONF_LINE_SECRET_MODIFIER_VISIBLE)
+        || is_char(token, CONF_LINE_SECRET_MODIFIER_LINEBREAK2)
+        || is_char(token, CONF_LINE_SECRET_MODIFIER_ESCAPE)
+}
+
+/// Is the token following `preceding` escaped? This is the case after an
+/// odd number of `CONF_LINE_SECRET_MODIFIER_ESCAPE`.
+fn is_escaped(preceding: &[String]) -> bool {
+    preceding
+        .iter()
+        .rev()
+        .take_while(|t| is_char(t, CONF_LINE_SECRET_MODIFIER_ESCAPE))
+        .count()
+        % 2
+        == 1
+}
 
 /// The character type.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 enum HangmanCharType {
     Visible,
     Hidden,
@@ -15,51 +332,73 @@ enum HangmanCharType {
     Ignored,
 }
 
-/// One character of the secret string.
-#[derive(Clone, Debug, PartialEq)]
+/// One character of the secret string: a grapheme cluster or a unit, see
+/// `SecretConf::tokenize()`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 struct HangmanChar {
-    character: char,
+    character: String,
     chartype: HangmanCharType,
 }
 
 /// The secret
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Secret {
     hangman_chars: Vec<HangmanChar>,
     chars_to_guess: usize,
+    conf: SecretConf,
 }
 
 impl Secret {
     /// Constructor.
-    pub fn new(secretstr: &str) -> Self {
+    pub fn new(secretstr: &str, conf: &SecretConf) -> Self {
         // parse `secretsstr`, flip 'visible' every CONF_LINE_SECRET_MODIFIER__VISIBLE
         let mut whitespace_on = false;
         let mut visible_on = false;
-        let w: Vec<HangmanChar> = secretstr
-            .chars()
+        let secret = conf.tokenize(secretstr);
+        let w: Vec<HangmanChar> = secret
+            .iter()
+            .enumerate()
             // For every `_` found flip `visible_on`.
             // CONF_LINE_SECRET_MODIFIER__LINEBREAK1 found, set `whitespace_on=true`.
             // Non whitespace found, set `whitespace_on=false`.
-            .map(|c| {
-                if whitespace_on && !c.is_whitespace() {
+            // Escaped characters are taken literally.
+            .map(|(i, c)| {
+                let escape = is_char(c, CONF_LINE_SECRET_MODIFIER_ESCAPE)
+                    && secret.get(i + 1).is_some_and(|n| is_escapable(n));
+                let escaped = is_escapable(c) && is_escaped(&secret[..i]);
+                if whitespace_on && !c.chars().all(char::is_whitespace) {
                     whitespace_on = false;
                 };
-                if (c == CONF_LINE_SECRET_MODIFIER_LINEBREAK1)
-                    || (c == CONF_LINE_SECRET_MODIFIER_LINEBREAK2)
+                if !escaped
+                    && (is_char(c, CONF_LINE_SECRET_MODIFIER_LINEBREAK1)
+                        || is_char(c, CONF_LINE_SECRET_MODIFIER_LINEBREAK2))
                 {
                     whitespace_on = true;
                 };
-                visible_on ^= c == CONF_LINE_SECRET_MODIFIER_VISIBLE;
+                visible_on ^= !escaped && is_char(c, CONF_LINE_SECRET_MODIFIER_VISIBLE);
                 let ct = match (c, visible_on, whitespace_on) {
-                    (CONF_LINE_SECRET_MODIFIER_VISIBLE, _, _) => HangmanCharType::Formatter,
-                    (CONF_LINE_SECRET_MODIFIER_LINEBREAK1, _, _) => HangmanCharType::Formatter,
-                    (CONF_LINE_SECRET_MODIFIER_LINEBREAK2, _, _) => HangmanCharType::Formatter,
+                    _ if escape && !escaped => HangmanCharType::Formatter,
+                    (_, true, _) if escaped => HangmanCharType::Visible,
+                    (_, false, _) if escaped => HangmanCharType::Hidden,
+                    _ if is_char(c, CONF_LINE_SECRET_MODIFIER_VISIBLE) => {
+                        HangmanCharType::Formatter
+                    }
+                    _ if is_char(c, CONF_LINE_SECRET_MODIFIER_LINEBREAK1) => {
+                        HangmanCharType::Formatter
+                    }
+                    _ if is_char(c, CONF_LINE_SECRET_MODIFIER_LINEBREAK2) => {
+                        HangmanCharType::Formatter
+                    }
                     (_, _, true) => HangmanCharType::Ignored,
                     (_, true, false) => HangmanCharType::Visible,
                     (_, false, false) => HangmanCharType::Hidden,
                 };
+                let ct = match ct {
+                    HangmanCharType::Hidden if conf.is_visible(c) => HangmanCharType::Visible,
+                    ct => ct,
+                };
                 HangmanChar {
-                    character: c,
+                    character: c.clone(),
                     chartype: ct,
                 }
             })
@@ -73,15 +412,24 @@ impl Secret {
         Self {
             hangman_chars: w,
             chars_to_guess,
+            conf: conf.clone(),
         }
     }
 
+    /// Maps a guessed token to the form it is compared with.
+    /// Tokens guessing the same are mapped to the same form.
+    pub fn fold(&self, token: &str) -> String {
+        self.conf.fold(token)
+    }
+
     /// Process a guess and modify the game state.
-    pub fn guess(&mut self, character: char) -> bool {
+    pub fn guess(&mut self, token: &str) -> bool {
         let mut found = false;
+        let token = self.fold(token);
+        let conf = &self.conf;
         for h_char in &mut self.hangman_chars {
             if matches!(h_char.chartype, HangmanCharType::Hidden)
-                && h_char.character.eq_ignore_ascii_case(&character)
+                && conf.fold(&h_char.character) == token
             {
                 h_char.chartype = HangmanCharType::Visible;
                 found = true;
@@ -91,6 +439,20 @@ impl Secret {
         found
     }
 
+    /// Discloses all occurrences of one randomly chosen hidden character.
+    /// Returns the disclosed character or `None`, when nothing is hidden.
+    pub fn disclose_random<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Option<String> {
+        let hidden: Vec<&String> = self
+            .hangman_chars
+            .iter()
+            .filter(|hc| matches!(hc.chartype, HangmanCharType::Hidden))
+            .map(|hc| &hc.character)
+            .collect();
+        let character = (*hidden.choose(rng)?).clone();
+        self.guess(&character);
+        Some(character)
+    }
+
     /// We disclose all characters when all lives are used and the
     /// game is over.
     pub fn disclose_all(&mut self) {
@@ -127,10 +489,52 @@ impl Secret {
         self.chars_to_guess
     }
 
+    /// Is every visible part closed with a second `_`? Escaped `_` are not counted.
+    pub fn is_balanced(&self) -> bool {
+        self.hangman_chars
+            .iter()
+            .filter(|hc| {
+                matches!(hc.chartype, HangmanCharType::Formatter)
+                    && is_char(&hc.character, CONF_LINE_SECRET_MODIFIER_VISIBLE)
+            })
+            .count()
+            % 2
+            == 0
+    }
+
+    /// Number of characters of the longest word. Lines break only between words.
+    pub fn longest_word(&self) -> usize {
+        self.hangman_chars
+            .split(|hc| {
+                hc.character.chars().all(char::is_whitespace)
+                    || (matches!(hc.chartype, HangmanCharType::Formatter)
+                        && is_char(&hc.character, CONF_LINE_SECRET_MODIFIER_LINEBREAK2))
+            })
+            .map(|word| {
+                word.iter()
+                    .filter(|hc| !matches!(hc.chartype, HangmanCharType::Formatter))
+                    .count()
+            })
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// The secret as plain text, without the `_` and `|` formatting characters.
+    pub fn to_plain_string(&self) -> String {
+        self.hangman_chars
+            .iter()
+            .filter(|hc| !matches!(hc.chartype, HangmanCharType::Formatter))
+            .map(|hc| hc.character.as_str())
+            .collect()
+    }
+
     /// Used in case the secret was not guessed and we want to inject
     /// it to the dictionary again.
     pub fn to_raw_string(&self) -> String {
-        self.hangman_chars.iter().map(|hc| hc.character).collect()
+        self.hangman_chars
+            .iter()
+            .map(|hc| hc.character.as_str())
+            .collect()
     }
 }
 
@@ -145,17 +549,17 @@ impl fmt::Display for Secret {
                 linebreak = true
             };
             if matches!(c.chartype, HangmanCharType::Formatter)
-                && (c.character == CONF_LINE_SECRET_MODIFIER_LINEBREAK1
-                    || c.character == CONF_LINE_SECRET_MODIFIER_LINEBREAK2)
+                && (is_char(&c.character, CONF_LINE_SECRET_MODIFIER_LINEBREAK1)
+                    || is_char(&c.character, CONF_LINE_SECRET_MODIFIER_LINEBREAK2))
             {
                 linebreak = true
             };
 
             if linebreak
-                && (!matches!(c.chartype, HangmanCharType::Formatter) && (c.character == ' ')
+                && (!matches!(c.chartype, HangmanCharType::Formatter) && (c.character == " ")
                     || (matches!(c.chartype, HangmanCharType::Formatter)
-                        && ((c.character == CONF_LINE_SECRET_MODIFIER_LINEBREAK1)
-                            || (c.character == CONF_LINE_SECRET_MODIFIER_LINEBREAK2))))
+                        && (is_char(&c.character, CONF_LINE_SECRET_MODIFIER_LINEBREAK1)
+                            || is_char(&c.character, CONF_LINE_SECRET_MODIFIER_LINEBREAK2))))
             {
                 linebreak = false;
                 n = 0;
@@ -186,21 +590,21 @@ mod tests {
     /// Game simulation
     #[test]
     fn test_secret() {
-        let mut secret = Secret::new("_ab _cd");
+        let mut secret = Secret::new("_ab _cd", &SecretConf::default());
 
         assert_eq!(secret.to_raw_string(), "_ab _cd");
         assert_eq!(format!("{}", secret), " a b   _ _\n");
         assert_eq!(secret.hidden_chars(), 2);
         assert!(!secret.is_fully_disclosed());
 
-        secret.guess('x');
+        secret.guess("x");
 
         assert_eq!(secret.to_raw_string(), "_ab _cd");
         assert_eq!(format!("{}", secret), " a b   _ _\n");
         assert_eq!(secret.hidden_chars(), 2);
         assert!(!secret.is_fully_disclosed());
 
-        secret.guess('d');
+        secret.guess("d");
 
         assert_eq!(secret.to_raw_string(), "_ab _cd");
         assert_eq!(format!("{}", secret), " a b   _ d\n");
@@ -209,13 +613,14 @@ mod tests {
 
         secret.disclose_all();
         assert_eq!(secret.to_raw_string(), "_ab _cd");
+        assert_eq!(secret.to_plain_string(), "ab cd");
         assert_eq!(format!("{}", secret), " a b   c d\n");
         assert_eq!(secret.hidden_chars(), 0);
         assert!(secret.is_fully_disclosed());
     }
     #[test]
     fn test_secret_linebreak() {
-        let mut secret = Secret::new("_abc|def _hij|klm");
+        let mut secret = Secret::new("_abc|def _hij|klm", &SecretConf::default());
         assert_eq!(secret.to_string(), " a b c\n d e f   _ _ _\n _ _ _\n");
         assert_eq!(secret.to_raw_string(), "_abc|def _hij|klm");
         assert_eq!(secret.hidden_chars(), 6);
@@ -227,16 +632,232 @@ mod tests {
         assert!(secret.is_fully_disclosed());
         assert_eq!(secret.to_raw_string(), "_abc|def _hij|klm");
 
-        let secret = Secret::new("_123456789012345 789012345 789012_");
+        let secret = Secret::new("_123456789012345 789012345 789012_", &SecretConf::default());
         assert_eq!(
             secret.to_string(),
             " 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5   7 8 9 0 1 2 3 4 5\n 7 8 9 0 1 2\n"
         );
 
-        let secret = Secret::new("_abc|  def _hij| \n  klm");
+        let secret = Secret::new("_abc|  def _hij| \n  klm", &SecretConf::default());
         assert_eq!(secret.to_string(), " a b c\n d e f   _ _ _\n\n _ _ _\n");
         assert_eq!(secret.to_raw_string(), "_abc|  def _hij| \n  klm");
         assert_eq!(secret.hidden_chars(), 6);
         assert!(!secret.is_fully_disclosed());
     }
+
+    /// Case-insensitive guesses in several scripts
+    #[test]
+    fn test_secret_case_folding() {
+        let conf = SecretConf::default();
+
+        // German
+        let mut secret = Secret::new("\u{c4}pfel", &conf);
+        assert!(secret.guess("\u{e4}"));
+        assert_eq!(secret.to_string(), " \u{c4} _ _ _ _\n");
+        let mut secret = Secret::new("Stra\u{df}e", &conf);
+        assert!(secret.guess("s"));
+        assert!(secret.guess("\u{1e9e}"));
+        assert_eq!(secret.to_string(), " S _ _ _ \u{df} _\n");
+
+        // Greek, including final sigma
+        let mut secret = Secret::new("\u{3a3}\u{3bf}\u{3c6}\u{3bf}\u{3c2}", &conf);
+        assert!(secret.guess("\u{3c3}"));
+        assert!(secret.guess("\u{39f}"));
+        assert_eq!(secret.hidden_chars(), 1);
+        assert_eq!(secret.to_string(), " \u{3a3} \u{3bf} _ \u{3bf} \u{3c2}\n");
+
+        // Cyrillic
+        let mut secret = Secret::new("\u{414}\u{43e}\u{43c}", &conf);
+        assert!(secret.guess("\u{434}"));
+        assert!(secret.guess("\u{41e}"));
+        assert!(!secret.guess("\u{44f}"));
+        assert_eq!(secret.to_string(), " \u{414} \u{43e} _\n");
+
+        // Without Turkish rules `I` and `i` match, the dotless `\u{131}` does not.
+        let mut secret = Secret::new("Istanbul", &conf);
+        assert!(!secret.guess("\u{131}"));
+        assert!(secret.guess("i"));
+    }
+
+    /// Turkish dotted and dotless i
+    #[test]
+    fn test_secret_case_folding_turkic() {
+        let conf = SecretConf::from_yaml("secrets:\n- x\nlocale: tr\n").unwrap();
+        assert_eq!(conf.case_folding, CaseFolding::Turkic);
+
+        let mut secret = Secret::new("Istanbul", &conf);
+        assert!(!secret.guess("i"));
+        assert!(secret.guess("\u{131}"));
+        assert_eq!(secret.to_string(), " I _ _ _ _ _ _ _\n");
+
+        let mut secret = Secret::new("\u{130}zmir", &conf);
+        assert!(!secret.guess("I"));
+        assert!(secret.guess("i"));
+        assert_eq!(secret.to_string(), " \u{130} _ _ i _\n");
+
+        assert_eq!(CaseFolding::from_locale("az-Latn"), CaseFolding::Turkic);
+        assert_eq!(CaseFolding::from_locale("de"), CaseFolding::Default);
+        assert_eq!(
+            SecretConf::from_yaml("guess me\n").unwrap(),
+            SecretConf::default()
+        );
+    }
+
+    /// Escaped `_`, `|` and `\\` are taken literally.
+    #[test]
+    fn test_secret_escape() {
+        let mut secret = Secret::new("snake\\_case", &SecretConf::default());
+        assert_eq!(secret.to_string(), " _ _ _ _ _ _ _ _ _ _\n");
+        assert!(secret.guess("_"));
+        assert_eq!(secret.hidden_chars(), 9);
+        assert_eq!(secret.to_plain_string(), "snake_case");
+        assert_eq!(secret.to_raw_string(), "snake\\_case");
+        assert!(secret.is_balanced());
+
+        let mut secret = Secret::new("_a\\|b_ or x\\\\_y_", &SecretConf::default());
+        assert_eq!(secret.to_string(), " a | b _ _ _ _ _ _ y\n");
+        assert_eq!(secret.to_plain_string(), "a|b or x\\y");
+        secret.disclose_all();
+        assert_eq!(secret.to_string(), " a | b   o r   x \\ y\n");
+        assert_eq!(secret.to_raw_string(), "_a\\|b_ or x\\\\_y_");
+
+        // Other characters are not escaped.
+        let secret = Secret::new("C:\\temp", &SecretConf::default());
+        assert_eq!(secret.to_plain_string(), "C:\\temp");
+        assert_eq!(secret.chars_to_guess(), 7);
+
+        // The raw string round-trips, e.g. when the secret is asked again.
+        for raw in ["snake\\_case", "a\\|b", "_x\\\\_", "\\\\\\_"] {
+            let secret = Secret::new(raw, &SecretConf::default());
+            let again = Secret::new(&secret.to_raw_string(), &SecretConf::default());
+            assert_eq!(secret, again);
+        }
+
+        assert!(!Secret::new("hang_ man", &SecretConf::default()).is_balanced());
+    }
+
+    /// Characters of the `visible:` classes are not guessed.
+    #[test]
+    fn test_secret_visible() {
+        let conf = SecretConf::from_yaml(
+            "secrets:\n- x\nvisible: [whitespace, punctuation]\nvisible-chars: \"0\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            conf.visible,
+            [CharClass::Whitespace, CharClass::Punctuation]
+        );
+
+        let secret = Secret::new("guess me, 2010!", &conf);
+        assert_eq!(secret.to_string(), " _ _ _ _ _   _ _ ,   _ 0 _ 0 !\n");
+        assert_eq!(secret.chars_to_guess(), 9);
+
+        let conf = SecretConf {
+            visible: vec![CharClass::Digits],
+            ..Default::default()
+        };
+        let secret = Secret::new("route 66", &conf);
+        assert_eq!(secret.to_string(), " _ _ _ _ _ _ 6 6\n");
+        assert_eq!(secret.chars_to_guess(), 6);
+
+        let err = SecretConf::from_yaml("secrets:\n- x\nvisible: [letters]\n").unwrap_err();
+        assert!(matches!(err, ConfigParseError::NotInYamlFormat(_)));
+    }
+
+    /// Only letters of the `alphabet:` are guessed.
+    #[test]
+    fn test_secret_alphabet() {
+        let conf = SecretConf::from_yaml("secrets:\n- x\nalphabet: latin\n").unwrap();
+        assert_eq!(conf.alphabet, Some(Alphabet::Latin));
+        assert!(conf.is_guessable("\u{e9}"));
+        assert!(!conf.is_guessable("7"));
+        assert!(!conf.is_guessable("\u{3b1}"));
+        let secret = Secret::new("d\u{e9}j\u{e0} vu?", &conf);
+        assert_eq!(secret.to_string(), " _ _ _ _   _ _ ?\n");
+        assert_eq!(secret.chars_to_guess(), 6);
+
+        let conf = SecretConf::from_yaml("secrets:\n- x\nalphabet: greek\n").unwrap();
+        assert!(conf.is_guessable("\u{3a9}"));
+        assert!(!conf.is_guessable("w"));
+
+        let conf = SecretConf::from_yaml("secrets:\n- x\nalphabet: cyrillic\n").unwrap();
+        assert!(conf.is_guessable("\u{416}"));
+
+        let conf = SecretConf::from_yaml("secrets:\n- x\nalphabet: {custom: abc}\n").unwrap();
+        assert_eq!(conf.alphabet, Some(Alphabet::Custom("abc".to_string())));
+        assert!(conf.is_guessable("B"));
+        assert!(!conf.is_guessable("d"));
+
+        assert!(SecretConf::default().is_guessable(" "));
+
+        // Preset names are not case-sensitive, unknown ones are refused.
+        let conf = SecretConf::from_yaml("secrets:\n- x\nalphabet: Latin\n").unwrap();
+        assert_eq!(conf.alphabet, Some(Alphabet::Latin));
+        let err = SecretConf::from_yaml("secrets:\n- x\nalphabet: latn\n").unwrap_err();
+        assert!(matches!(err, ConfigParseError::UnknownAlphabet { .. }));
+    }
+
+    /// Accent-insensitive guesses
+    #[test]
+    fn test_secret_ignore_accents() {
+        // Composed and decomposed forms match, accents are strict by default.
+        let conf = SecretConf::default();
+        let mut secret = Secret::new("cafe\u{301}", &conf);
+        assert!(!secret.guess("e"));
+        assert!(secret.guess("\u{c9}"));
+        assert_eq!(secret.to_string(), " _ _ _ \u{e9}\n");
+        assert_eq!(secret.to_raw_string(), "caf\u{e9}");
+
+        let conf = SecretConf::from_yaml("secrets:\n- x\nignore-accents: true\n").unwrap();
+        assert!(conf.ignore_accents);
+
+        let mut secret = Secret::new("\u{e9}l\u{e8}ve \u{ea}tre No\u{eb}l", &conf);
+        assert!(secret.guess("E"));
+        assert_eq!(secret.hidden_chars(), 9);
+
+        let mut secret = Secret::new("Espan\u{303}a", &conf);
+        assert!(secret.guess("n"));
+        assert_eq!(secret.to_string(), " _ _ _ _ \u{f1} _\n");
+
+        // An accented guess matches the base letter too.
+        let mut secret = Secret::new("nino", &conf);
+        assert!(secret.guess("\u{f1}"));
+        assert_eq!(secret.hidden_chars(), 2);
+    }
+
+    /// Multi-character units and grapheme clusters are guessed as one.
+    #[test]
+    fn test_secret_units() {
+        let conf = SecretConf::from_yaml("secrets:\n- x\nunits: [ij, ll, d\u{17e}]\n").unwrap();
+        assert_eq!(conf.tokenize("IJsje"), ["IJ", "s", "j", "e"]);
+        assert_eq!(conf.tokenize("lil"), ["l", "i", "l"]);
+
+        let mut secret = Secret::new("IJsje calle", &conf);
+        assert_eq!(secret.chars_to_guess(), 9);
+        assert!(!secret.guess("i"));
+        assert!(secret.guess("ij"));
+        assert!(!secret.guess("l"));
+        assert!(secret.guess("LL"));
+        assert_eq!(secret.to_string(), " IJ _ _ _ _ _ _ ll _\n");
+
+        // Decomposed input is composed before it is split.
+        let mut secret = Secret::new("dz\u{30c}ep", &conf);
+        assert_eq!(secret.chars_to_guess(), 3);
+        assert!(secret.guess("D\u{17d}"));
+
+        // Combining marks without a composed form and emoji are never split.
+        let conf = SecretConf::default();
+        assert_eq!(conf.tokenize("q\u{301}a"), ["q\u{301}", "a"]);
+        let mut secret = Secret::new("\u{1f469}\u{200d}\u{1f467} a", &conf);
+        assert_eq!(secret.chars_to_guess(), 3);
+        assert!(secret.guess("\u{1f469}\u{200d}\u{1f467}"));
+        assert_eq!(secret.to_raw_string(), "\u{1f469}\u{200d}\u{1f467} a");
+
+        // A custom alphabet may list units.
+        let conf = SecretConf::from_yaml("secrets:\n- x\nunits: [ch]\nalphabet: {custom: abcch}\n")
+            .unwrap();
+        assert!(conf.is_guessable("ch"));
+        assert!(conf.is_guessable("c"));
+        assert!(!conf.is_guessable("x"));
+    }
 }
@@ -0,0 +1,130 @@
+//! Records how the secrets of a session were played, so that teachers can see
+//! which vocabulary the class struggled with.
+
+use serde_derive::{Deserialize, Serialize};
+
+/// Statistics about one played secret.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoundReport {
+    /// The secret string as found in the configuration.
+    pub secret: String,
+    /// Number of guesses not found in the secret. Repeated guesses are not counted.
+    pub wrong_guesses: usize,
+    /// The guesses not found in the secret.
+    pub missed: Vec<String>,
+    /// Was the round lost?
+    pub defeat: bool,
+    /// Was the secret put back into the list to be asked again?
+    pub requeued: bool,
+    /// Time taken to play the round.
+    pub seconds: u64,
+}
+
+/// Statistics about all secrets played in a session.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct SessionReport {
+    pub rounds: Vec<RoundReport>,
+}
+
+impl SessionReport {
+    /// Comma separated values with one header line and one line per round.
+    /// The missed guesses are separated by spaces, as a guess can be a unit.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("secret,wrong_guesses,missed,defeat,requeued,seconds\n");
+        for r in &self.rounds {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                csv_field(&r.secret),
+                r.wrong_guesses,
+                csv_field(&r.missed.join(" ")),
+                r.defeat,
+                r.requeued,
+                r.seconds
+            ));
+        }
+        csv
+    }
+
+    /// The report in JSON format.
+    pub fn to_json(&self) -> String {
+        // Serializing plain strings, numbers and booleans does not fail.
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+/// Quotes a CSV field when needed.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Seconds since the Unix epoch.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Seconds since the Unix epoch.
+/// `std::time` is not available in the browser.
+#[cfg(target_arch = "wasm32")]
+pub fn now() -> u64 {
+    (js_sys::Date::now() / 1000.0) as u64
+}
+
+// ***********************
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_formats() {
+        let report = SessionReport {
+            rounds: vec![
+                RoundReport {
+                    secret: "guess me".to_string(),
+                    wrong_guesses: 2,
+                    missed: vec!["x".to_string(), "y".to_string()],
+                    defeat: false,
+                    requeued: false,
+                    seconds: 12,
+                },
+                RoundReport {
+                    secret: "lama".to_string(),
+                    wrong_guesses: 2,
+                    missed: vec!["ll".to_string(), "x".to_string()],
+                    defeat: false,
+                    requeued: false,
+                    seconds: 5,
+                },
+                RoundReport {
+                    secret: "\"_der Hund:_, the dog\"".to_string(),
+                    wrong_guesses: 7,
+                    missed: vec!["a".to_string()],
+                    defeat: true,
+                    requeued: true,
+                    seconds: 30,
+                },
+            ],
+        };
+
+        assert_eq!(
+            report.to_csv(),
+            "secret,wrong_guesses,missed,defeat,requeued,seconds\n\
+             guess me,2,x y,false,false,12\n\
+             lama,2,ll x,false,false,5\n\
+             \"\"\"_der Hund:_, the dog\"\"\",7,a,true,true,30\n"
+        );
+
+        let json = report.to_json();
+        assert!(json.contains("\"wrong_guesses\": 2"));
+        let restored: SessionReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, report);
+    }
+}
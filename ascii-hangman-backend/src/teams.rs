@@ -0,0 +1,135 @@
+//! Team competition: the teams play alternate secrets. A team scores the
+//! characters it disclosed and the lives it has left.
+use crate::dictionary::has_yaml_key;
+use crate::dictionary::is_yaml_config;
+use crate::dictionary::ConfigParseError;
+use crate::game::Game;
+use serde_derive::{Deserialize, Serialize};
+
+/// A team and its points.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Team {
+    pub name: String,
+    pub score: usize,
+}
+
+/// The competing teams and which one plays the current secret.
+/// Without teams, there is no competition.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct Teams {
+    teams: Vec<Team>,
+    /// Index of the team playing the current secret.
+    current: usize,
+}
+
+impl Teams {
+    /// Constructor. Empty names are ignored.
+    pub fn new(names: &[String]) -> Self {
+        Self {
+            teams: names
+                .iter()
+                .map(|n| n.trim())
+                .filter(|n| !n.is_empty())
+                .map(|n| Team {
+                    name: n.to_string(),
+                    score: 0,
+                })
+                .collect(),
+            current: 0,
+        }
+    }
+
+    /// Constructor reading the `teams:` variable from YAML configuration
+    /// files. Configuration files in the legacy format have no teams.
+    pub fn from_yaml(input: &str) -> Result<Self, ConfigParseError> {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct RawTeams {
+            teams: Option<Vec<String>>,
+        }
+
+        let input = input.trim_start_matches('\u{feff}');
+        if !is_yaml_config(input) && !has_yaml_key(input, "teams") {
+            return Ok(Self::default());
+        }
+
+        let raw: RawTeams = serde_yaml::from_str(input)?;
+
+        Ok(Self::new(&raw.teams.unwrap_or_default()))
+    }
+
+    /// The team playing the current secret.
+    pub fn current(&self) -> Option<&Team> {
+        self.teams.get(self.current)
+    }
+
+    /// Credits the current team with the points of the finished `game`:
+    /// one per character disclosed by a correct guess and one per remaining life.
+    /// Characters disclosed by hints do not score.
+    pub fn score(&mut self, game: &Game) {
+        if let Some(team) = self.teams.get_mut(self.current) {
+            team.score += game.revealed - game.hinted + game.lifes as usize;
+        }
+    }
+
+    /// The next secret is played by the next team.
+    pub fn next(&mut self) {
+        if !self.teams.is_empty() {
+            self.current = (self.current + 1) % self.teams.len();
+        }
+    }
+
+    /// All teams in the order of the configuration.
+    pub fn iter(&self) -> impl Iterator<Item = &Team> {
+        self.teams.iter()
+    }
+}
+
+// ***********************
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secret::SecretConf;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_teams() {
+        let config = "secrets:\n- guess me\nteams: [Red, Blue]\n";
+        let mut teams = Teams::from_yaml(config).unwrap();
+        assert_eq!(teams.current().unwrap().name, "Red");
+
+        let mut game = Game::new("_ab _cd", &SecretConf::default(), 3, false);
+        game.guess("c");
+        game.guess("x");
+        game.guess("d");
+        teams.score(&game);
+        teams.next();
+        assert_eq!(teams.current().unwrap().name, "Blue");
+
+        let mut game = Game::new("cd", &SecretConf::default(), 1, false);
+        game.guess("c");
+        game.guess("x");
+        teams.score(&game);
+        teams.next();
+        assert_eq!(teams.current().unwrap().name, "Red");
+
+        let scores: Vec<usize> = teams.iter().map(|t| t.score).collect();
+        assert_eq!(scores, vec![4, 1]);
+
+        // Letters disclosed by a hint do not score.
+        let mut teams = Teams::from_yaml("secrets:\n- guess me\nteams: [Red]\n").unwrap();
+        let mut game = Game::new("cd", &SecretConf::default(), 3, false);
+        game.hint(1, &mut StdRng::seed_from_u64(1));
+        assert_eq!(game.revealed, 1);
+        game.guess("c");
+        game.guess("d");
+        teams.score(&game);
+        assert_eq!(teams.current().unwrap().score, 3);
+
+        let mut teams = Teams::from_yaml("secrets:\n- guess me\n").unwrap();
+        teams.score(&game);
+        teams.next();
+        assert_eq!(teams.current(), None);
+    }
+}
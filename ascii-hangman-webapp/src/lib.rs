@@ -5,18 +5,28 @@
 #![recursion_limit = "512"]
 
 use ascii_hangman_backend::game::State;
+use ascii_hangman_backend::statistics::now;
 use ascii_hangman_backend::Backend;
 use ascii_hangman_backend::HangmanBackend;
-use ascii_hangman_backend::{AUTHOR, CONF_TEMPLATE, CONF_TEMPLATE_SHORT, TITLE, VERSION};
+use ascii_hangman_backend::{
+    AUTHOR, CONF_TEMPLATE, CONF_TEMPLATE_SHORT, HINT_REQUEST, TITLE, VERSION,
+};
+use std::time::Duration;
 use wasm_bindgen::prelude::*;
 use yew::events::KeyboardEvent;
+use yew::format::Text;
 use yew::prelude::*;
+use yew::services::interval::{IntervalService, IntervalTask};
 use yew::services::reader::{File, FileData, ReaderService, ReaderTask};
+use yew::services::storage::{Area, StorageService};
 // Disable debugging code.
 //use yew::services::ConsoleService;
 use yew::services::DialogService;
 use yew::{html, Component, ComponentLink, Html, InputData, ShouldRender};
 
+/// Key under which the session is saved in the browser's local storage.
+const SESSION_KEY: &str = "ascii-hangman.session";
+
 #[derive(Debug)]
 pub enum Scene {
     Playground(Backend),
@@ -27,6 +37,8 @@ pub enum Scene {
 pub struct GuiState {
     config_text: String,
     guess: String,
+    /// The chosen entry of `lessons:`, if any.
+    lesson: Option<String>,
 }
 
 pub struct Model {
@@ -34,6 +46,10 @@ pub struct Model {
     // Disable debugging code.
     //console: ConsoleService,
     filereader_tasks: Vec<ReaderTask>,
+    /// The browser's local storage, when available.
+    storage: Option<StorageService>,
+    /// Sends `Msg::Tick` every second to drive the `timer:`.
+    _interval: IntervalTask,
     scene: Scene,
     state: GuiState,
 }
@@ -44,10 +60,14 @@ pub enum Msg {
     ConfigTextDelete,
     ConfigTextUpdate(String),
     ConfigReady,
+    SelectLesson(String),
+    Resume,
     Files(Vec<File>),
     Loaded(FileData),
     UpdateGuess(String),
     Guess,
+    Hint,
+    Tick,
     Nope,
     NextRound,
 }
@@ -60,13 +80,18 @@ impl Component for Model {
         let state = GuiState {
             config_text: String::from(CONF_TEMPLATE_SHORT),
             guess: String::new(),
+            lesson: None,
         };
 
+        let interval = IntervalService::spawn(Duration::from_secs(1), link.callback(|_| Msg::Tick));
+
         Model {
             link,
             // Disable debugging code.
             //console: ConsoleService::new(),
             filereader_tasks: vec![],
+            storage: StorageService::new(Area::Local).ok(),
+            _interval: interval,
             scene: Scene::ConfigureGame,
             state,
         }
@@ -74,6 +99,8 @@ impl Component for Model {
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         let mut new_scene = None;
+        // Only a guess or a timeout changes the session worth saving.
+        let mut save = false;
         match &mut self.scene {
             Scene::Playground(ref mut app) => match msg {
                 Msg::SwitchTo(Scene::ConfigureGame) => {
@@ -83,13 +110,27 @@ impl Component for Model {
                     new_scene = Some(Scene::GameOver);
                 }
                 Msg::UpdateGuess(val) => {
-                    self.state.guess = val.chars().rev().take(1).collect();
+                    // Keep the whole input, a unit like `ij` has more than one character.
+                    self.state.guess = val;
                     // Disable debugging code.
                     //self.console.debug(&self.state.guess);
                 }
                 Msg::Guess => {
                     app.process_user_input(&self.state.guess);
                     self.state.guess = String::new();
+                    save = true;
+                }
+                Msg::Hint => {
+                    app.process_user_input(&HINT_REQUEST.to_string());
+                    save = true;
+                }
+                Msg::Tick => {
+                    // A timeout costs a life.
+                    let lifes = app.render_game_lifes();
+                    if !app.process_time(now()) {
+                        return false;
+                    }
+                    save = app.render_game_lifes() != lifes;
                 }
                 Msg::Nope => {}
                 unexpected => {
@@ -118,7 +159,11 @@ impl Component for Model {
 
                 Msg::Loaded(file) => {
                     if let Ok(s) = std::str::from_utf8(&file.content) {
-                        self.state.config_text.push_str(s);
+                        let sources = [("input", self.state.config_text.as_str()), (&file.name, s)];
+                        match Backend::merge_configs(&sources) {
+                            Ok(config) => self.state.config_text = config,
+                            Err(e) => DialogService::alert(&e.to_string()),
+                        }
                     } else {
                         DialogService::alert(&format!("Can not read text file: {}", file.name));
                     }
@@ -132,8 +177,32 @@ impl Component for Model {
                         self.filereader_tasks.push(task);
                     }
                 }
+                Msg::Resume => {
+                    if let Some(Ok(session)) = self.saved_session() {
+                        match Backend::restore(&session) {
+                            Ok(app) => {
+                                self.link
+                                    .send_message(Msg::SwitchTo(Scene::Playground(app)));
+                            }
+                            Err(e) => {
+                                DialogService::alert(&format!("Can not resume game:\n {}", e))
+                            }
+                        };
+                    }
+                }
+                Msg::Tick => return false,
+                Msg::SelectLesson(lesson) => {
+                    self.state.lesson = Some(lesson);
+                }
                 Msg::ConfigReady => {
-                    match Backend::new(self.state.config_text.as_str()) {
+                    // The chosen lesson may have disappeared from the configuration since.
+                    let lessons = Backend::lessons(&self.state.config_text).unwrap_or_default();
+                    let lesson = self.state.lesson.as_ref().filter(|l| lessons.contains(l));
+                    match Backend::with_lesson(
+                        &self.state.config_text,
+                        lesson.map(|l| l.as_str()),
+                        None,
+                    ) {
                         Ok(app) => {
                             self.link
                                 .send_message(Msg::SwitchTo(Scene::Playground(app)));
@@ -154,6 +223,7 @@ impl Component for Model {
                 Msg::SwitchTo(Scene::ConfigureGame) => {
                     new_scene = Some(Scene::ConfigureGame);
                 }
+                Msg::Tick => return false,
                 unexpected => {
                     panic!("Unexpected message for settings scene: {:?}", unexpected);
                 }
@@ -162,6 +232,9 @@ impl Component for Model {
         if let Some(new_scene) = new_scene.take() {
             self.scene = new_scene;
         }
+        if save {
+            self.save_session();
+        }
         true
     }
 
@@ -210,10 +283,13 @@ impl Component for Model {
                             })/>
                     </div>
 
+                    { self.view_lessons() }
                     <button disabled=self.state.config_text.is_empty()
                             onclick=self.link.callback(|_| Msg::ConfigTextDelete)>{ "Delete Secrets" }</button>
                     <button disabled=self.state.config_text.is_empty()
                             onclick=self.link.callback(|_| Msg::ConfigReady)>{ "Start Game" }</button>
+                    <button disabled=!matches!(self.saved_session(), Some(Ok(_)))
+                            onclick=self.link.callback(|_| Msg::Resume)>{ "Resume Game" }</button>
                 </div>
                 {footer()}
                 </>
@@ -243,6 +319,26 @@ impl Component for Model {
                             { app.render_game_last_guess() }
                         </th>
                         </tr>
+                        <tr>
+                        <th colspan=2>
+                            { app.render_timer(now()) }
+                        </th>
+                        </tr>
+                        <tr>
+                        <th colspan=2>
+                            { app.render_game_missed_guesses() }
+                        </th>
+                        </tr>
+                        <tr>
+                        <th colspan=2>
+                            { app.render_player_scores() }
+                        </th>
+                        </tr>
+                        <tr>
+                        <th colspan=2>
+                            { app.render_current_player() }
+                        </th>
+                        </tr>
                         </table>
                             <textarea class="secret"
                                 cols=format!("{}", cols+1)
@@ -250,6 +346,12 @@ impl Component for Model {
                                 value=secret
                                 readonly=true
                             />
+                        <div class="secret-info"> { app.render_hint() } </div>
+                        <div class="secret-info"> { app.render_translation() } </div>
+                        <div class="scoreboard"> { app.render_review() } </div>
+                        <div class="scoreboard">
+                            { if app.get_state() != State::Ongoing { app.render_scoreboard() } else { String::new() } }
+                        </div>
                         <div class="instructions">
                             { app.render_instructions() }
                             <input class="guess"
@@ -263,11 +365,13 @@ impl Component for Model {
                                 }) />
 
                         </div>
-                        <button disabled={app.get_state() == State::Ongoing || app.get_state() == State::VictoryGameOver}
+                        <button disabled={!app.is_hint_available()}
+                                onclick=self.link.callback(|_| Msg::Hint)>{ "Hint" }</button>
+                        <button disabled={app.get_state() == State::Ongoing || app.is_game_over()}
                                 onclick=self.link.callback(|_| Msg::Guess)>{ "Continue Game" }</button>
-                        <button disabled={app.get_state() != State::VictoryGameOver}
+                        <button disabled={!app.is_game_over()}
                                 onclick=self.link.callback(|_| Msg::SwitchTo(Scene::ConfigureGame))>{ "Reset Game" }</button>
-                        <button disabled={app.get_state() != State::VictoryGameOver}
+                        <button disabled={!app.is_game_over()}
                                 onclick=self.link.callback(|_| Msg::SwitchTo(Scene::GameOver))>{ "End Game" }</button>
                     </div>
                     {footer()}
@@ -288,6 +392,55 @@ impl Component for Model {
     }
 }
 
+impl Model {
+    /// A dropdown list to choose one of the `lessons:`. Empty when there are none.
+    fn view_lessons(&self) -> Html {
+        let lessons = Backend::lessons(&self.state.config_text).unwrap_or_default();
+        if lessons.is_empty() {
+            return html! {};
+        }
+        let selected = self
+            .state
+            .lesson
+            .clone()
+            .filter(|l| lessons.contains(l))
+            .unwrap_or_else(|| lessons[0].clone());
+        html! {
+            <div class="upload-container"> { "Lesson: " }
+                <select onchange=self.link.callback(|e: ChangeData| match e {
+                        ChangeData::Select(select) => Msg::SelectLesson(select.value()),
+                        _ => Msg::Nope,
+                    })>
+                    { for lessons.iter().map(|l| html! {
+                        <option value=l.clone() selected={*l == selected}> { l } </option>
+                    }) }
+                </select>
+            </div>
+        }
+    }
+
+    /// The session saved in the browser's local storage.
+    fn saved_session(&self) -> Option<Text> {
+        self.storage
+            .as_ref()
+            .map(|storage| storage.restore(SESSION_KEY))
+    }
+
+    /// Keeps the session in the browser's local storage up to date,
+    /// so that the game can be resumed after the browser was closed.
+    /// A finished session is removed.
+    fn save_session(&mut self) {
+        if let (Scene::Playground(app), Some(storage)) = (&self.scene, &mut self.storage) {
+            if app.is_game_over() {
+                storage.remove(SESSION_KEY);
+            } else {
+                let session: Text = app.save().map_err(|e| e.into());
+                storage.store(SESSION_KEY, session);
+            }
+        }
+    }
+}
+
 /// Returns the columns and lines of the smallest
 /// grid that can display this multi-line string `s`.
 pub fn dimensions(s: &str) -> (usize, usize) {
@@ -3,26 +3,34 @@
 #![cfg(not(target_arch = "wasm32"))]
 
 use ascii_hangman_backend::game::State;
+use ascii_hangman_backend::statistics::now;
 use ascii_hangman_backend::Backend;
+use ascii_hangman_backend::ConfigParseError;
 use ascii_hangman_backend::HangmanBackend;
 use ascii_hangman_backend::{AUTHOR, CONF_TEMPLATE, TITLE, VERSION};
 use std::env;
+use std::fs;
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
 use std::io::Write;
+use std::iter;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process;
+use std::time::Duration;
 
 use crossterm::cursor::MoveTo;
 use crossterm::cursor::MoveToNextLine;
+use crossterm::event;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use crossterm::queue;
 use crossterm::style::Color;
 use crossterm::style::Print;
 #[cfg(not(target_os = "windows"))]
 use crossterm::style::ResetColor;
 use crossterm::style::SetForegroundColor;
+use crossterm::terminal;
 use crossterm::terminal::Clear;
 use crossterm::terminal::ClearType;
 use std::io::stdout;
@@ -62,6 +70,11 @@ Ascii-Art images - designed for children - are progressively disclosed.
 ```
     Usage: ascii-hangman
            ascii-hangman [FILE]
+           ascii-hangman --resume SESSION [FILE]
+           ascii-hangman --report REPORT [FILE]
+           ascii-hangman --seed NUMBER [FILE]
+           ascii-hangman --players [FILE]
+           ascii-hangman --check [FILE]
            ascii-hangman -h|--help
            ascii-hangman -V|--version
 ```
@@ -71,19 +84,93 @@ optionally one Ascii-Art image.
 
 When no `[FILE]` argument is given, `[FILE]` defaults to `ascii-hangman-words.txt`. In case no
 `[FILE]` is found, a template configuration file `ascii-hangman-words.txt` is written into the
-current working directory. Multiple `[FILE]`s are concatenated.
+current working directory. `ascii-hangman-words.txt` is searched in the current working
+directory first, then in the directories listed in the environment variable `ASCII_HANGMAN_PATH`
+and finally in the directory `ascii-hangman` of the user's configuration directory, e.g.
+`~/.config/ascii-hangman/` under Linux.
 
-`[FILE]` is a UTF-8 YAML formatted file containing 3 different variables:
+Multiple `[FILE]`s are merged: their `secrets:` and `lessons:` are united, the last `image:` wins
+and all other variables must have the same value in every `[FILE]` that sets them, e.g. one file
+can not be `traditional: true` when another is `traditional: false`.
+
+With `--resume SESSION` the game state is saved after every guess in the file `SESSION`. When
+`SESSION` exists at start, the saved game continues where it stopped and `[FILE]` is ignored.
+
+With `--report REPORT` statistics about every played secret are written in the file `REPORT`
+when all secrets are guessed: the wrong guesses, whether the secret was lost and the time taken.
+The format is JSON, when `REPORT` ends with `.json`, and CSV otherwise.
+
+With `--seed NUMBER` all random choices, e.g. the order of the secrets, the images and the hints,
+are derived from `NUMBER`. The same `NUMBER` and `[FILE]` replay exactly the same lesson.
+
+With `--players` the names of the players are asked for at start. They replace the names
+configured with `players:`.
+
+With `--check` the `[FILE]`s are checked without playing: every problem found is printed, e.g.
+a syntax error, a secret without characters to guess, an odd number of `_`, a duplicate secret,
+a word too long for the display or an image bigger than 65535x65535 characters. The exit status is
+1 when there are problems, and 0 otherwise.
+
+`[FILE]` is a UTF-8 YAML formatted file containing the following variables:
 
 - `secrets:` is an array of secrets, one per line. A secret is a string, that interprets the `|`
   character as newline and the `_` character as visibility switch. This switch allows to
-  disclose a part of the secret when the game starts.
+  disclose a part of the secret when the game starts. A `\` before `_`, `|` or `\` makes
+  it an ordinary character to guess, e.g. `snake\_case`. Inside `"` quotes, write `\\_`.
+  Instead of a string, a secret can be a mapping with the string in `text:` and the optional
+  keys `hint:`, `translation:`, `category:`, `difficulty:` and `weight:`. The hint and the
+  translation are shown when the game is over. The `weight:` is a number making a secret more
+  (e.g. `3`) or less (`0`) likely to be chosen. Default is `1`.
+- `order:` optionally determines in which order the secrets are asked:
+  * `random`: every time a secret is chosen randomly (default),
+  * `sequential`: in the order of the `secrets:` list, e.g. from easy to hard,
+  * `shuffle-once`: in an order shuffled once at start.
+- `lessons:` is an optional mapping from lesson names to arrays of secrets, like `secrets:`.
+  At start, a menu lets you choose the lesson to play. Its secrets replace `secrets:`, which is
+  optional when `lessons:` is given.
 - `traditional:` is an optional boolean variable indicating how the ASCII-art image should be
   disclosed:
   * `true`: the image gets disclosed with every lost life,
   * `false`: the image gets disclosed with every guessed character (default).
+- `lives:` is an optional number of wrong guesses allowed, between 1 and 30 (default: 7).
+- `hint-cost:` is the optional number of lives a hint costs (default: 1). It must be less than
+  `lives:`. Type `?` to get a hint, which discloses one hidden letter. Hints are not given when
+  only one life is left. A `?` in a secret is always shown.
 - `image: |1` is an optional multiline string providing own ASCII-art. When missing, built-in
   ASCII-art is chosen randomly.
+- `locale:` is an optional language tag, e.g. `tr`, selecting language specific rules for
+  matching upper and lower case guesses, e.g. Turkish dotted and dotless `i`.
+- `players:` is an optional array of player names. The players take turns: a player keeps the
+  turn after a correct or a repeated guess and passes it on after a miss. Who discloses the last
+  letter of the secret, wins the game and scores.
+- `random-turns:` is an optional boolean variable. When `true`, the turn passes to a randomly
+  chosen player instead of the next one. Default is `false`.
+- `teams:` is an optional array of team names. The teams play alternate secrets. A team scores
+  one point for every letter it guesses and one for every life left. The scoreboard is shown
+  after every secret.
+- `timer:` is the optional number of seconds allowed per guess. When the time runs out, a life
+  is lost like with a wrong guess. The seconds left are shown next to the lives.
+- `defeat:` optionally determines what happens with a secret that was not guessed:
+  * `requeue`: it is asked again later (default),
+  * `requeue-last`: it is asked again after all other secrets,
+  * a number, e.g. `2`: it is asked again at most 2 times,
+  * `drop`: it is not asked again, but listed for review when the session is over.
+- `visible:` is an optional array of character classes, that are shown from the start instead of
+  being guessed: `whitespace`, `punctuation` (including other symbols) and `digits`. E.g. with
+  `visible: [whitespace, punctuation]` only the letters of `guess me!` are guessed.
+- `visible-chars:` is an optional string of characters, that are shown from the start, e.g. `"-'"`.
+- `alphabet:` optionally restricts the guesses to the letters of an alphabet: `latin`, `greek`,
+  `cyrillic` or the letters of your own alphabet, e.g. `alphabet: {custom: "abcdefg"}`. Other
+  characters are refused without losing a life, and are shown from the start in the secrets.
+- `units:` is an optional array of letters written with more than one character, e.g.
+  `units: [ij, ll, ch]`. They are shown and guessed as one letter: in `ijs` the `ij` is guessed
+  by typing `ij`, not `i`. Letters with combining accents and emoji are always guessed as one.
+- `ignore-accents:` is an optional boolean variable. When `true`, a guess discloses all accented
+  forms of a letter, e.g. `e` discloses `é`, `è`, `ê` and `ë`. Default is `false`.
+- `include:` is an optional array of file names, e.g. shared word lists. The files are merged
+  with `[FILE]` like multiple `[FILE]`s given on the command line. Relative names are relative to
+  the directory of the including file. Included files may include other files, but not
+  themselves.
 - Lines starting with `#` are ignored.
 
 Example:
@@ -135,7 +222,12 @@ To learn more about the YAML format visit: <https://en.wikipedia.org/wiki/YAML>
 
 /// Default configuration filename when no filename is given at the command-line.
 const PATHSTR: &str = "ascii-hangman-words.txt";
-/// Fallback sample configuration when no configuration file can be found.
+
+/// Environment variable listing directories to search for `PATHSTR`.
+const PATH_ENV: &str = "ASCII_HANGMAN_PATH";
+
+/// Directory in the user's configuration directory to search for `PATHSTR`.
+const CONFIG_DIR: &str = "ascii-hangman";
 
 /// Fallback secret when no configuration file can be found.
 const CONF_DEMO: &str = "secrets:\n - \"_Demo: add own words to config file and start a_gain_!\"";
@@ -157,41 +249,28 @@ pub fn write_config_template(pathstr: PathBuf) -> Result<(), io::Error> {
     Ok(())
 }
 
-/// Starts the game.
-/// This is the terminal application
-#[allow(unused_labels)]
-fn main() {
-    // SHOW HELP TEXT
-    match env::args().nth(1) {
-        Some(ref a) if a == "-h" || a == "--help" => {
-            eprintln!("{}", COMMANDLINE_HELP);
-            return;
-        }
-        Some(ref a) if a == "-V" || a == "--version" => {
-            eprintln!("{}", VERSION.unwrap());
-            return;
-        }
-        Some(_) | None => {}
-    };
-
-    // READ CONFIG
-
-    // Read all config files given on command line
-    let mut conf_file_paths = env::args()
-        .skip(1)
-        .map(PathBuf::from)
-        .collect::<Vec<PathBuf>>();
-
-    // if no conf_file_paths are given then use default config path
-    if conf_file_paths.is_empty() {
-        conf_file_paths.push(PathBuf::from(PATHSTR))
-    };
+/// Searches `PATHSTR` in the current working directory, in the directories
+/// listed in `PATH_ENV` and in `CONFIG_DIR` of the user's configuration
+/// directory. When it is nowhere, its path in the current working directory is returned.
+fn default_config_path() -> PathBuf {
+    let env_dirs = env::var_os(PATH_ENV)
+        .map(|dirs| env::split_paths(&dirs).collect::<Vec<_>>())
+        .unwrap_or_default();
+    iter::once(PathBuf::new())
+        .chain(env_dirs)
+        .chain(dirs::config_dir().map(|dir| dir.join(CONFIG_DIR)))
+        .map(|dir| dir.join(PATHSTR))
+        .find(|path| path.is_file())
+        .unwrap_or_else(|| PathBuf::from(PATHSTR))
+}
 
-    // read and concatenate all config files given on command line
+/// Reads all configuration files and pairs them with their names.
+/// Missing files are replaced by a template.
+fn read_configs(conf_file_paths: &[PathBuf]) -> Vec<(String, String)> {
     let cwd = env::current_dir().unwrap();
 
-    let mut config: String = String::new();
-    for conf_file_path in &conf_file_paths {
+    let mut configs = Vec::new();
+    for conf_file_path in conf_file_paths {
         let path = conf_file_path;
         let c = match read_config(path) {
             Ok(s) => s,
@@ -217,9 +296,7 @@ fn main() {
                              config-file:\n\t{:?}\n({})\n\n\
                              Current working directory is:\n\t{:?}\n\n\
                              Press [Enter] to enter demo mode.",
-                            path,
-                            why.to_string(),
-                            cwd
+                            path, why, cwd
                         );
                         // wait for [Enter] key
                         let s = &mut String::new();
@@ -229,15 +306,242 @@ fn main() {
                 }
             }
         };
-        config.push_str(&c);
+        configs.push((path.display().to_string(), c));
+    }
+    configs
+}
+
+/// Reads the files included by `configs` and merges all into one configuration.
+fn merge_configs(configs: &[(String, String)]) -> Result<String, ConfigParseError> {
+    let mut read = |path: &Path| fs::read_to_string(path);
+    let mut all = Vec::new();
+    for (file, config) in configs {
+        all.extend(Backend::resolve_includes(file, config, &mut read)?);
+    }
+    let sources: Vec<(&str, &str)> = all
+        .iter()
+        .map(|(file, config)| (file.as_str(), config.as_str()))
+        .collect();
+    Backend::merge_configs(&sources)
+}
+
+/// Checks every configuration file, together with the files it includes,
+/// without playing. Prints the problems found and returns `false` when there are some.
+fn check_configs(conf_file_paths: &[PathBuf]) -> bool {
+    let mut ok = true;
+    for path in conf_file_paths {
+        let file = path.display().to_string();
+        let problems = match read_config(path) {
+            Ok(config) => match merge_configs(&[(file.clone(), config)]) {
+                Ok(config) => Backend::check(&config),
+                Err(e) => vec![e.to_string()],
+            },
+            Err(e) => vec![format!("Can not read the file: {}", e)],
+        };
+        if problems.is_empty() {
+            println!("{}: ok", file);
+        }
+        for problem in problems {
+            ok = false;
+            println!("{}: {}", file, problem);
+        }
+    }
+    ok
+}
+
+/// Shows a menu with the `lessons:` of the configuration and returns the chosen one.
+/// Returns `None`, when there are no lessons.
+fn choose_lesson(config: &str) -> Option<String> {
+    let lessons = Backend::lessons(config).ok()?;
+    if lessons.is_empty() {
+        return None;
+    }
+    println!("Choose a lesson:");
+    for (i, lesson) in lessons.iter().enumerate() {
+        println!("  {}) {}", i + 1, lesson);
+    }
+    loop {
+        print!("Type a number, then press [Enter]: ");
+        io::stdout().flush().unwrap();
+        let s = &mut String::new();
+        if io::stdin().read_line(s).unwrap() == 0 {
+            return None;
+        }
+        let chosen = s
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_sub(1))
+            .and_then(|i| lessons.get(i));
+        if let Some(lesson) = chosen {
+            return Some(lesson.clone());
+        }
+    }
+}
+
+/// Writes the session state on disk, so that it can be continued with `--resume`.
+/// The file is removed when the session is over.
+pub fn write_session(path: &Path, app: &Backend) -> Result<(), io::Error> {
+    if app.is_game_over() {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            r => r,
+        };
+    }
+    let session = app.save().map_err(|e| io::Error::other(e.to_string()))?;
+    let mut file = File::create(path)?;
+    file.write_all(session.as_bytes())?;
+    Ok(())
+}
+
+/// Writes the session statistics on disk, as JSON when the filename ends with `.json`,
+/// as CSV otherwise.
+pub fn write_report(path: &Path, app: &Backend) -> Result<(), io::Error> {
+    let report = app.get_report();
+    let report = if path.extension().is_some_and(|e| e == "json") {
+        report.to_json()
+    } else {
+        report.to_csv()
+    };
+    let mut file = File::create(path)?;
+    file.write_all(report.as_bytes())?;
+    Ok(())
+}
+
+/// Reads a line from the terminal while the `timer:` runs. Returns `None`, when
+/// the time display must be updated before the line is complete. `line` keeps
+/// the characters typed so far.
+fn read_line_timed(app: &mut Backend, line: &mut String) -> Option<String> {
+    terminal::enable_raw_mode().unwrap();
+    let result = loop {
+        if event::poll(Duration::from_millis(200)).unwrap() {
+            if let Event::Key(KeyEvent { code, modifiers }) = event::read().unwrap() {
+                match code {
+                    KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                        terminal::disable_raw_mode().unwrap();
+                        process::exit(130);
+                    }
+                    KeyCode::Char(c) => {
+                        line.push(c);
+                        queue!(stdout(), Print(c)).unwrap();
+                    }
+                    KeyCode::Backspace if line.pop().is_some() => {
+                        queue!(stdout(), Print("\u{8} \u{8}")).unwrap();
+                    }
+                    KeyCode::Enter => break Some(std::mem::take(line) + "\n"),
+                    _ => {}
+                }
+                stdout().flush().unwrap();
+            }
+        } else {
+            if app.process_time(now()) {
+                break None;
+            }
+        }
+    };
+    terminal::disable_raw_mode().unwrap();
+    result
+}
+
+/// Starts the game.
+/// This is the terminal application
+#[allow(unused_labels)]
+fn main() {
+    // SHOW HELP TEXT
+    match env::args().nth(1) {
+        Some(ref a) if a == "-h" || a == "--help" => {
+            eprintln!("{}", COMMANDLINE_HELP);
+            return;
+        }
+        Some(ref a) if a == "-V" || a == "--version" => {
+            eprintln!("{}", VERSION.unwrap());
+            return;
+        }
+        Some(_) | None => {}
+    };
+
+    // READ CONFIG
+
+    // Read all config files given on command line and the options.
+    let mut conf_file_paths: Vec<PathBuf> = Vec::new();
+    let mut session_path: Option<PathBuf> = None;
+    let mut report_path: Option<PathBuf> = None;
+    let mut seed: Option<u64> = None;
+    let mut ask_players = false;
+    let mut check = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--resume" {
+            match args.next() {
+                Some(path) => session_path = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("The option `--resume` requires a SESSION file.");
+                    process::exit(1);
+                }
+            }
+        } else if arg == "--report" {
+            match args.next() {
+                Some(path) => report_path = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("The option `--report` requires a REPORT file.");
+                    process::exit(1);
+                }
+            }
+        } else if arg == "--players" {
+            ask_players = true;
+        } else if arg == "--check" {
+            check = true;
+        } else if arg == "--seed" {
+            match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) => seed = Some(n),
+                None => {
+                    eprintln!("The option `--seed` requires a NUMBER.");
+                    process::exit(1);
+                }
+            }
+        } else {
+            conf_file_paths.push(PathBuf::from(arg));
+        }
+    }
+
+    // if no conf_file_paths are given then use default config path
+    if conf_file_paths.is_empty() {
+        conf_file_paths.push(default_config_path())
+    };
+
+    // CHECK THE CONFIG
+
+    if check {
+        let ok = check_configs(&conf_file_paths);
+        process::exit(if ok { 0 } else { 1 });
     }
 
     // INITIALISE THE GAME
 
-    let mut app = match Backend::new(&config) {
+    // Continue a saved session when there is one.
+    let session = session_path
+        .as_deref()
+        .and_then(|path| read_config(path).ok());
+
+    let app = match &session {
+        Some(session) => Backend::restore(session),
+        None => merge_configs(&read_configs(&conf_file_paths)).and_then(|config| {
+            let lesson = choose_lesson(&config);
+            Backend::with_lesson(&config, lesson.as_deref(), seed)
+        }),
+    };
+
+    let mut app = match app {
         Ok(d) => d,
         Err(e) => {
-            eprintln!("ERROR IN CONFIGURATION FILE\n{}", e);
+            match (&session_path, &session) {
+                (Some(path), Some(_)) => eprintln!(
+                    "ERROR IN SAVED SESSION {:?}\n{}\n\
+                     Delete the file to start a new session.",
+                    path, e
+                ),
+                _ => eprintln!("ERROR IN CONFIGURATION FILE\n{}", e),
+            }
 
             // wait for [Enter] key
             let s = &mut String::new();
@@ -246,23 +550,52 @@ fn main() {
         }
     };
 
+    if ask_players && session.is_none() {
+        println!("Names of the players, separated by commas:");
+        let names = &mut String::new();
+        io::stdin().read_line(names).unwrap();
+        let names: Vec<String> = names.split(',').map(|n| n.to_string()).collect();
+        app.set_players(&names);
+    }
+
     app.render();
+    if let Some(path) = &session_path {
+        if let Err(e) = write_session(path, &app) {
+            eprintln!("\nCouldn't save the session: {:?} ({})", path, e);
+        }
+    }
 
     // PLAY
 
+    // Characters typed while the `timer:` runs.
+    let mut typed = String::new();
     'playing: loop {
         // Read user input
         io::stdout().flush().unwrap();
-        // Read next char and send it
-        let key = &mut String::new();
-        io::stdin().read_line(key).unwrap();
+        // Read next char and send it. Without `timer:` nothing changes while
+        // typing, so the terminal keeps its own line editing and piped input works.
+        let key = if app.get_state() == State::Ongoing && !app.render_timer(now()).is_empty() {
+            read_line_timed(&mut app, &mut typed)
+        } else {
+            let key = &mut String::new();
+            io::stdin().read_line(key).unwrap();
+            Some(key.to_string())
+        };
 
-        app.process_user_input(key);
+        if let Some(key) = key {
+            app.process_user_input(&key);
+        }
         app.render();
+        queue!(stdout(), Print(&typed)).unwrap();
         io::stdout().flush().unwrap();
+        if let Some(path) = &session_path {
+            if let Err(e) = write_session(path, &app) {
+                eprintln!("\nCouldn't save the session: {:?} ({})", path, e);
+            }
+        }
         let state = app.get_state();
         if state != State::Ongoing {
-            if state != State::VictoryGameOver {
+            if !app.is_game_over() {
                 queue!(
                     stdout(),
                     MoveToNextLine(1),
@@ -276,6 +609,18 @@ fn main() {
         };
     }
 
+    if let Some(path) = &report_path {
+        match write_report(path, &app) {
+            Ok(()) => queue!(
+                stdout(),
+                MoveToNextLine(1),
+                Print(format!("Report written to {:?}.", path))
+            )
+            .unwrap(),
+            Err(e) => eprintln!("\nCouldn't write the report: {:?} ({})", path, e),
+        }
+    }
+
     queue!(stdout(), MoveToNextLine(1), Print(AUTHOR)).unwrap();
     stdout().flush().unwrap();
     let key = &mut String::new();
@@ -317,12 +662,25 @@ impl Render for Backend {
         #[cfg(windows)]
         queue!(stdout(), SetForegroundColor(Color::Grey),).unwrap();
         queue!(stdout(), Print(self.render_game_lifes()), Print("\t")).unwrap();
+        queue!(stdout(), Print(self.render_game_last_guess())).unwrap();
+        let timer = self.render_timer(now());
+        if !timer.is_empty() {
+            queue!(stdout(), Print("\t"), Print(timer)).unwrap();
+        }
         queue!(
             stdout(),
-            Print(self.render_game_last_guess()),
-            MoveToNextLine(2)
+            MoveToNextLine(1),
+            Print(self.render_game_missed_guesses()),
+            MoveToNextLine(1)
         )
         .unwrap();
+        // Print players. They are available when configured.
+        for info in [self.render_player_scores(), self.render_current_player()] {
+            if !info.is_empty() {
+                queue!(stdout(), Print(info), MoveToNextLine(1)).unwrap();
+            }
+        }
+        queue!(stdout(), MoveToNextLine(1)).unwrap();
 
         // Print secret.
         #[cfg(not(windows))]
@@ -337,6 +695,25 @@ impl Render for Backend {
         #[cfg(windows)]
         queue!(stdout(), SetForegroundColor(Color::Grey),).unwrap();
 
+        // Print hint and translation. They are available when the game is over.
+        for info in [self.render_hint(), self.render_translation()] {
+            if !info.is_empty() {
+                queue!(stdout(), Print(info), MoveToNextLine(1)).unwrap();
+            }
+        }
+
+        // Print the secrets to practise again, when the session is over.
+        for line in self.render_review().lines() {
+            queue!(stdout(), Print(line), MoveToNextLine(1)).unwrap();
+        }
+
+        // Print the scoreboard between the secrets.
+        if self.get_state() != State::Ongoing {
+            for line in self.render_scoreboard().lines() {
+                queue!(stdout(), Print(line), MoveToNextLine(1)).unwrap();
+            }
+        }
+
         queue!(stdout(), Print(self.render_instructions()), Print(" ")).unwrap();
         // Print queued.
         stdout().flush().unwrap();